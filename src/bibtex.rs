@@ -0,0 +1,686 @@
+//! Minimal [BibTeX](http://www.bibtex.org/Format/) support, used to import reading lists as share
+//! posts.
+
+use crate::{Error, Share};
+
+use std::collections::{BTreeMap, HashMap};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// BibTeX entry, such as `@article{key, title = {...}}`.
+#[derive(Debug)]
+pub struct Entry {
+    /// Entry type, lowercased, such as `article` or `inproceedings`.
+    pub entry_type: String,
+
+    /// Citation key.
+    pub key: String,
+
+    /// Entry fields, with lowercased names. Values keep their inner braces.
+    pub fields: BTreeMap<String, String>,
+}
+
+impl Entry {
+    /// Returns field value, with braces removed and whitespace collapsed.
+    pub fn field(&self, name: &str) -> Option<String> {
+        self.fields
+            .get(name)
+            .map(|v| clean_value(v))
+            .filter(|v| !v.is_empty())
+    }
+
+    /// Maps entry to a [Share] post.
+    ///
+    /// The share URI is taken from the `url` field, falling back to the DOI and then to the arXiv
+    /// ID, if any.
+    pub fn to_share(&self) -> Result<Share, Error> {
+        let title = self
+            .field("title")
+            .ok_or_else(|| Error::InputError(format!("entry \"{}\" has no title", self.key)))?;
+
+        let authors = self
+            .fields
+            .get("author")
+            .map(|a| split_authors(a))
+            .unwrap_or_default();
+        let authors: Vec<_> = authors.iter().map(String::as_str).collect();
+
+        let tags: Vec<_> = self
+            .field("keywords")
+            .map(|k| {
+                k.split([',', ';'])
+                    .map(str::trim)
+                    .filter(|t| !t.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let tags: Vec<_> = tags.iter().map(String::as_str).collect();
+
+        let doi = self.field("doi").map(|d| {
+            let d = d.trim_start_matches("https://doi.org/");
+            String::from(d.trim_start_matches("http://dx.doi.org/"))
+        });
+
+        let is_arxiv = ["archiveprefix", "eprinttype"].iter().any(|&f| {
+            self.field(f)
+                .map(|p| p.eq_ignore_ascii_case("arxiv"))
+                .unwrap_or(false)
+        });
+        let arxiv_id = if is_arxiv { self.field("eprint") } else { None };
+
+        let uri = self
+            .field("url")
+            .or_else(|| doi.as_ref().map(|d| format!("https://doi.org/{}", d)))
+            .or_else(|| {
+                arxiv_id
+                    .as_ref()
+                    .map(|a| format!("https://arxiv.org/abs/{}", a))
+            })
+            .ok_or_else(|| {
+                Error::InputError(format!("entry \"{}\" has no url, doi or eprint", self.key))
+            })?;
+
+        let year = match self.field("year") {
+            None => None,
+            Some(y) => Some(y.parse().map_err(|_| {
+                Error::InputError(format!("entry \"{}\" has invalid year \"{}\"", self.key, y))
+            })?),
+        };
+
+        let mut share = Share::new(&title, &authors, &tags, &uri);
+        share.doi = doi;
+        share.arxiv_id = arxiv_id;
+        share.year = year;
+        share.venue = self.field("journal").or_else(|| self.field("booktitle"));
+        share.abstract_text = self.field("abstract");
+
+        Ok(share)
+    }
+}
+
+/// Parses BibTeX entries from `input`.
+///
+/// `@string` macros and `#` concatenations are resolved, `@comment` and `@preamble` blocks are
+/// ignored.
+pub fn parse(input: &str) -> Result<Vec<Entry>, Error> {
+    let mut parser = Parser::new(input);
+    let mut entries = Vec::new();
+
+    while parser.skip_to_entry() {
+        if let Some(entry) = parser.entry()? {
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries)
+}
+
+/// LaTeX accent commands, with their Unicode combining mark and the precomposed characters of
+/// common accented letters, as pairs of letter and accented letter.
+const ACCENTS: &[(&str, char, &str)] = &[
+    (
+        "'",
+        '\u{301}',
+        "AÁEÉIÍOÓUÚYÝaáeéıíiíoóuúyýCĆcćLĹlĺNŃnńRŔrŕSŚsśZŹzź",
+    ),
+    ("`", '\u{300}', "AÀEÈIÌOÒUÙaàeèıìiìoòuù"),
+    (
+        "^",
+        '\u{302}',
+        "AÂEÊIÎOÔUÛaâeêıîiîoôuûCĈcĉGĜgĝHĤhĥJĴȷĵjĵSŜsŝWŴwŵYŶyŷ",
+    ),
+    ("\"", '\u{308}', "AÄEËIÏOÖUÜYŸaäeëıïiïoöuüyÿ"),
+    ("~", '\u{303}', "AÃIĨNÑOÕUŨaãıĩiĩnñoõuũ"),
+    ("=", '\u{304}', "AĀEĒIĪOŌUŪaāeēıīiīoōuū"),
+    (".", '\u{307}', "CĊEĖGĠIİZŻcċeėgġzż"),
+    ("u", '\u{306}', "AĂGĞUŬaăgğuŭ"),
+    ("v", '\u{30c}', "CČDĎEĚNŇRŘSŠTŤZŽcčdďeěnňrřsštťzž"),
+    ("H", '\u{30b}', "OŐUŰoőuű"),
+    ("r", '\u{30a}', "AÅUŮaåuů"),
+    ("c", '\u{327}', "CÇSŞTŢcçsştţ"),
+    ("k", '\u{328}', "AĄEĘIĮUŲaąeęiįuų"),
+];
+
+/// LaTeX commands of letters and of special characters, such as the ones written by BibTeX
+/// output.
+const LETTERS: &[(&str, char)] = &[
+    ("&", '&'),
+    ("%", '%'),
+    ("$", '$'),
+    ("#", '#'),
+    ("_", '_'),
+    ("textbackslash", '\\'),
+    ("textbraceleft", '{'),
+    ("textbraceright", '}'),
+    ("textasciitilde", '~'),
+    ("textasciicircum", '^'),
+    ("i", 'ı'),
+    ("j", 'ȷ'),
+    ("ss", 'ß'),
+    ("o", 'ø'),
+    ("O", 'Ø'),
+    ("ae", 'æ'),
+    ("AE", 'Æ'),
+    ("oe", 'œ'),
+    ("OE", 'Œ'),
+    ("aa", 'å'),
+    ("AA", 'Å'),
+    ("l", 'ł'),
+    ("L", 'Ł'),
+];
+
+/// Removes braces, turns LaTeX special characters, letters and accents, such as `\&`, `\ss` and
+/// `\'E`, into Unicode, and collapses whitespace of a field value. Unknown commands are kept.
+fn clean_value(value: &str) -> String {
+    let mut cleaned = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '}' => {}
+            '\\' => {
+                let command = latex_command(&mut chars);
+                if let Some((_, mark, letters)) = ACCENTS.iter().find(|(a, ..)| *a == command) {
+                    match accent_argument(&mut chars) {
+                        Some(letter) => cleaned.push_str(&accented(letter, *mark, letters)),
+                        None => cleaned.push(*mark),
+                    }
+                } else if let Some((_, letter)) = LETTERS.iter().find(|(l, _)| *l == command) {
+                    cleaned.push(*letter);
+                } else {
+                    cleaned.push('\\');
+                    cleaned.push_str(&command);
+                }
+            }
+            _ => cleaned.push(c),
+        }
+    }
+    cleaned.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Reads the name of a LaTeX command, after its backslash: either a word, which eats a single
+/// following space, or a single symbol.
+fn latex_command(chars: &mut Peekable<Chars>) -> String {
+    let mut command = String::new();
+    while let Some(&c) = chars.peek() {
+        if !c.is_ascii_alphabetic() {
+            break;
+        }
+        command.push(c);
+        chars.next();
+    }
+
+    if command.is_empty() {
+        command.extend(chars.next());
+    } else if chars.peek() == Some(&' ') {
+        chars.next();
+    }
+    command
+}
+
+/// Reads the letter argument of an accent command, such as the `E` of `\'E` or `\'{E}`, or the
+/// dotless `i` of `\'{\i}`.
+fn accent_argument(chars: &mut Peekable<Chars>) -> Option<char> {
+    while matches!(chars.peek(), Some('{') | Some(' ')) {
+        chars.next();
+    }
+
+    match chars.next()? {
+        '\\' => match latex_command(chars).as_str() {
+            "i" => Some('ı'),
+            "j" => Some('ȷ'),
+            _ => None,
+        },
+        '}' => None,
+        c => Some(c),
+    }
+}
+
+/// Returns `letter` with an accent, precomposed if on `letters`, or followed by the combining
+/// `mark` otherwise.
+fn accented(letter: char, mark: char, letters: &str) -> String {
+    let mut pairs = letters.chars();
+    while let (Some(l), Some(a)) = (pairs.next(), pairs.next()) {
+        if l == letter {
+            return a.to_string();
+        }
+    }
+
+    match letter {
+        'ı' => format!("i{}", mark),
+        'ȷ' => format!("j{}", mark),
+        _ => format!("{}{}", letter, mark),
+    }
+}
+
+/// Splits a BibTeX `author` field on top level `and` separators, turning `Last, First` names into
+/// `First Last`.
+fn split_authors(value: &str) -> Vec<String> {
+    let mut authors = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+
+    for word in value.split_whitespace() {
+        if depth == 0 && word.eq_ignore_ascii_case("and") {
+            authors.push(std::mem::take(&mut current));
+            continue;
+        }
+
+        for c in word.chars() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    authors.push(current);
+
+    authors
+        .iter()
+        .map(|a| match top_level_comma(a) {
+            Some(i) => format!("{} {}", clean_value(&a[i + 1..]), clean_value(&a[..i])),
+            None => clean_value(a),
+        })
+        .map(|a| String::from(a.trim()))
+        .filter(|a| !a.is_empty())
+        .collect()
+}
+
+/// Returns position of the first comma of `name` outside of braces, such as the comma of
+/// `Last, First`, but not the one of `{Barnes and Noble, Inc.}`.
+fn top_level_comma(name: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in name.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    strings: HashMap<String, String>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Parser<'a> {
+        let months = [
+            "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+        ];
+        let strings = months
+            .iter()
+            .enumerate()
+            .map(|(i, &m)| (String::from(m), (i + 1).to_string()))
+            .collect();
+
+        Parser {
+            input,
+            pos: 0,
+            strings,
+        }
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        let line = self.input[..self.pos].matches('\n').count() + 1;
+        Error::InputError(format!("invalid BibTeX on line {}: {}", line, msg))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map(char::is_whitespace).unwrap_or(false) {
+            self.bump();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Error> {
+        self.skip_whitespace();
+        match self.bump() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(self.error(&format!("expected '{}'", expected))),
+        }
+    }
+
+    /// Skips any text outside of entries, returns `false` if input has ended.
+    fn skip_to_entry(&mut self) -> bool {
+        match self.input[self.pos..].find('@') {
+            Some(i) => {
+                self.pos += i + 1;
+                true
+            }
+            None => {
+                self.pos = self.input.len();
+                false
+            }
+        }
+    }
+
+    fn identifier(&mut self) -> Result<&'a str, Error> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_alphanumeric() || "_-:.+/'".contains(c) {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+
+        if start == self.pos {
+            return Err(self.error("expected identifier"));
+        }
+        Ok(&self.input[start..self.pos])
+    }
+
+    /// Parses an entry after its `@`. Returns [None] for blocks which are not entries.
+    fn entry(&mut self) -> Result<Option<Entry>, Error> {
+        let entry_type = self.identifier()?.to_lowercase();
+
+        self.skip_whitespace();
+        let (open, close) = match self.bump() {
+            Some('{') => ('{', '}'),
+            Some('(') => ('(', ')'),
+            _ => return Err(self.error("expected '{' or '('")),
+        };
+
+        match entry_type.as_str() {
+            "comment" | "preamble" => {
+                self.balanced(open, close)?;
+                Ok(None)
+            }
+            "string" => {
+                let name = self.identifier()?.to_lowercase();
+                self.expect('=')?;
+                let value = self.value()?;
+                self.expect(close)?;
+                self.strings.insert(name, value);
+                Ok(None)
+            }
+            _ => {
+                self.skip_whitespace();
+                let start = self.pos;
+                while let Some(c) = self.peek() {
+                    if c == ',' || c == close || c.is_whitespace() {
+                        break;
+                    }
+                    self.bump();
+                }
+                let key = String::from(&self.input[start..self.pos]);
+                if key.is_empty() {
+                    return Err(self.error("missing citation key"));
+                }
+
+                let mut fields = BTreeMap::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.bump() {
+                        Some(c) if c == close => break,
+                        Some(',') => {}
+                        _ => return Err(self.error(&format!("expected ',' or '{}'", close))),
+                    }
+
+                    self.skip_whitespace();
+                    if self.peek() == Some(close) {
+                        self.bump();
+                        break;
+                    }
+
+                    let name = self.identifier()?.to_lowercase();
+                    self.expect('=')?;
+                    let value = self.value()?;
+                    fields.insert(name, value);
+                }
+
+                Ok(Some(Entry {
+                    entry_type,
+                    key,
+                    fields,
+                }))
+            }
+        }
+    }
+
+    /// Parses a field value, made of `#` separated parts.
+    fn value(&mut self) -> Result<String, Error> {
+        let mut value = String::new();
+
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('{') => {
+                    self.bump();
+                    value.push_str(self.balanced('{', '}')?);
+                }
+                Some('"') => {
+                    self.bump();
+                    value.push_str(self.balanced('"', '"')?);
+                }
+                Some(c) if c.is_ascii_digit() => {
+                    let start = self.pos;
+                    while self.peek().map(|c| c.is_ascii_digit()).unwrap_or(false) {
+                        self.bump();
+                    }
+                    value.push_str(&self.input[start..self.pos]);
+                }
+                _ => {
+                    let name = self.identifier()?.to_lowercase();
+                    let macro_value = self
+                        .strings
+                        .get(&name)
+                        .ok_or_else(|| self.error(&format!("undefined macro \"{}\"", name)))?;
+                    value.push_str(macro_value);
+                }
+            }
+
+            self.skip_whitespace();
+            if self.peek() == Some('#') {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+
+        Ok(value)
+    }
+
+    /// Consumes text until `close` is found outside of inner braces, after `open` was consumed.
+    /// Returns consumed text, without the delimiters.
+    fn balanced(&mut self, open: char, close: char) -> Result<&'a str, Error> {
+        let start = self.pos;
+        let mut depth = 0;
+
+        loop {
+            let c = self
+                .bump()
+                .ok_or_else(|| self.error(&format!("unclosed '{}'", open)))?;
+
+            if c == close && depth == 0 {
+                return Ok(&self.input[start..self.pos - c.len_utf8()]);
+            }
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_one(input: &str) -> Entry {
+        let mut entries = parse(input).unwrap();
+        assert_eq!(entries.len(), 1);
+        entries.remove(0)
+    }
+
+    #[test]
+    fn parses_entry_fields() {
+        let entry = parse_one(
+            r#"@Article{clarke2001,
+                Title = {Freenet: A {Distributed} Anonymous
+                         Information Storage},
+                year = 2001,
+                journal = "Designing Privacy",
+            }"#,
+        );
+
+        assert_eq!(entry.entry_type, "article");
+        assert_eq!(entry.key, "clarke2001");
+        assert_eq!(
+            entry.field("title").as_deref(),
+            Some("Freenet: A Distributed Anonymous Information Storage")
+        );
+        assert_eq!(entry.field("year").as_deref(), Some("2001"));
+        assert_eq!(entry.field("journal").as_deref(), Some("Designing Privacy"));
+    }
+
+    #[test]
+    fn resolves_strings_and_concatenations() {
+        let entry = parse_one(
+            r#"@string{ieee = "IEEE Transactions"}
+            @STRING(net = {on Networking})
+            @article{key, journal = ieee # " " # net, month = jan # "--" # feb}"#,
+        );
+
+        assert_eq!(
+            entry.field("journal").as_deref(),
+            Some("IEEE Transactions on Networking")
+        );
+        assert_eq!(entry.field("month").as_deref(), Some("1--2"));
+    }
+
+    #[test]
+    fn ignores_comments_and_text_outside_entries() {
+        let entries = parse(
+            r#"Reading list, exported on Monday.
+            @comment{@article{hidden, title = {Hidden}}}
+            @preamble{"\newcommand{\noop}[1]{}"}
+            @misc(shown, title = {Shown})"#,
+        )
+        .unwrap();
+
+        let keys: Vec<_> = entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, vec!["shown"]);
+    }
+
+    #[test]
+    fn splits_authors() {
+        let entry = parse_one(
+            r#"@misc{key, author = {Clarke, Ian and Oskar Sandberg AND
+                {Barnes and Noble, Inc.} and {\'E}mile Zola and Doe,}}"#,
+        );
+
+        assert_eq!(
+            split_authors(&entry.fields["author"]),
+            vec![
+                "Ian Clarke",
+                "Oskar Sandberg",
+                "Barnes and Noble, Inc.",
+                "Émile Zola",
+                "Doe",
+            ]
+        );
+    }
+
+    #[test]
+    fn unescapes_latex_special_characters() {
+        let entry = parse_one(r"@misc{key, title = {Profit \& Loss of 5\% \#1}}");
+
+        assert_eq!(
+            entry.field("title").as_deref(),
+            Some("Profit & Loss of 5% #1")
+        );
+    }
+
+    #[test]
+    fn converts_latex_accents() {
+        for (value, cleaned) in &[
+            (r"{\'E}mile", "Émile"),
+            (r"\'{E}mile", "Émile"),
+            (r#"G{\"o}del and Erd\H{o}s"#, "Gödel and Erdős"),
+            (r"Fran\c{c}ois Fran\c cois", "François François"),
+            (r"\v{S}koda \`a la \^ile", "Škoda à la île"),
+            (r"Ni\~no Ni{\~{n}}o", "Niño Niño"),
+            (r"{\'\i}ndice \'{\i}ndice", "índice índice"),
+            (r"Stra\ss e {\O}resund \aa{}", "Straße Øresund å"),
+            (r"50\% \textbraceleft{}x\textbraceright{}", "50% {x}"),
+            (r"\'x \cite{key}", "x\u{301} \\citekey"),
+        ] {
+            assert_eq!(clean_value(value), *cleaned, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn maps_entry_to_share() {
+        let share = parse_one(
+            r#"@inproceedings{key,
+                title = {Freenet}, author = {Clarke, Ian}, year = {2001},
+                booktitle = {Workshop}, keywords = {p2p; anonymity, storage},
+                doi = {https://doi.org/10.1007/3-540-44702-4_4},
+            }"#,
+        )
+        .to_share()
+        .unwrap();
+
+        assert_eq!(share.title, "Freenet");
+        assert_eq!(share.authors, vec!["Ian Clarke"]);
+        assert_eq!(share.tags, vec!["p2p", "anonymity", "storage"]);
+        assert_eq!(share.year, Some(2001));
+        assert_eq!(share.venue.as_deref(), Some("Workshop"));
+        assert_eq!(share.doi.as_deref(), Some("10.1007/3-540-44702-4_4"));
+        assert_eq!(share.uri, "https://doi.org/10.1007/3-540-44702-4_4");
+    }
+
+    #[test]
+    fn maps_arxiv_eprint_to_share_uri() {
+        let share = parse_one(
+            r#"@misc{key, title = {Paper}, eprint = {2101.00001}, archivePrefix = {arXiv}}"#,
+        )
+        .to_share()
+        .unwrap();
+
+        assert_eq!(share.arxiv_id.as_deref(), Some("2101.00001"));
+        assert_eq!(share.uri, "https://arxiv.org/abs/2101.00001");
+    }
+
+    #[test]
+    fn rejects_invalid_entries() {
+        for input in &[
+            "@article{key, title = {Unclosed}",
+            "@article{key, title = undefined}",
+            "@article{key title = {No comma}}",
+            "@article{, title = {No key}}",
+            "@article key",
+        ] {
+            assert!(parse(input).is_err(), "{:?} was parsed", input);
+        }
+
+        let no_uri = parse_one("@misc{key, title = {Title}}");
+        assert!(no_uri.to_share().is_err());
+        let bad_year = parse_one("@misc{key, title = {Title}, url = {x}, year = {2k}}");
+        assert!(bad_year.to_share().is_err());
+        let no_title = parse_one("@misc{key, url = {x}}");
+        assert!(no_title.to_share().is_err());
+    }
+}
@@ -0,0 +1,531 @@
+//! Rendering of [Share] posts into citation formats.
+
+use crate::{Error, SearchResult, Share};
+use serde_json::json;
+
+use std::collections::HashSet;
+use std::io;
+use std::str::FromStr;
+
+/// Output format for a list of shares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Post hashes and reputations, one per line, followed by an indented line with the full text
+    /// search snippet, if any.
+    Hashes,
+
+    /// JSON array of shares, each one with its post hash and reputation.
+    Json,
+
+    /// Shares as JSON objects, one per line, each one with its post hash and reputation.
+    JsonLines,
+
+    /// BibTeX entries.
+    Bibtex,
+
+    /// RIS records.
+    Ris,
+
+    /// [CSL-JSON](https://citeproc-js.readthedocs.io/en/latest/csl-json/markup.html) array.
+    CslJson,
+}
+
+impl Format {
+    /// Names accepted by [FromStr].
+    pub const NAMES: &'static [&'static str] =
+        &["hashes", "json", "jsonl", "bibtex", "ris", "csljson"];
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hashes" => Ok(Format::Hashes),
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::JsonLines),
+            "bibtex" => Ok(Format::Bibtex),
+            "ris" => Ok(Format::Ris),
+            "csljson" => Ok(Format::CslJson),
+            _ => Err(Error::InputError(format!("unknown format \"{}\"", s))),
+        }
+    }
+}
+
+/// Writes search `results` on the given `format`.
+pub fn write(mut w: impl io::Write, format: Format, results: &[SearchResult]) -> Result<(), Error> {
+    match format {
+        Format::Hashes => {
+            for result in results {
+                writeln!(w, "{} {}", result.hash, result.reps)?;
+                if let Some(snippet) = &result.snippet {
+                    writeln!(w, "    {}", snippet)?;
+                }
+            }
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut w, results)?;
+            writeln!(w)?;
+        }
+        Format::JsonLines => {
+            for result in results {
+                serde_json::to_writer(&mut w, result)?;
+                writeln!(w)?;
+            }
+        }
+        Format::Bibtex => {
+            let mut keys = HashSet::new();
+            for (i, SearchResult { share, .. }) in results.iter().enumerate() {
+                if i > 0 {
+                    writeln!(w)?;
+                }
+                let key = unique_key(&mut keys, citation_key(share));
+                write_bibtex(&mut w, &key, share)?;
+            }
+        }
+        Format::Ris => {
+            for SearchResult { hash, share, .. } in results {
+                write_ris(&mut w, hash, share)?;
+            }
+        }
+        Format::CslJson => {
+            let items: Vec<_> = results
+                .iter()
+                .map(|SearchResult { hash, share, .. }| csl_item(hash, share))
+                .collect();
+            serde_json::to_writer_pretty(&mut w, &items)?;
+            writeln!(w)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes `share` as a BibTeX entry with citation `key`.
+fn write_bibtex(mut w: impl io::Write, key: &str, share: &Share) -> Result<(), Error> {
+    let entry_type = if share.venue.is_some() {
+        "article"
+    } else {
+        "misc"
+    };
+    writeln!(w, "@{}{{{},", entry_type, key)?;
+
+    let mut fields = vec![("title", share.title.clone())];
+    if !share.authors.is_empty() {
+        let authors: Vec<_> = share.authors.iter().map(|a| strip_email(a)).collect();
+        fields.push(("author", authors.join(" and ")));
+    }
+    if let Some(year) = share.year {
+        fields.push(("year", year.to_string()));
+    }
+    if let Some(venue) = &share.venue {
+        fields.push(("journal", venue.clone()));
+    }
+    if let Some(doi) = &share.doi {
+        fields.push(("doi", doi.clone()));
+    }
+    if let Some(arxiv_id) = &share.arxiv_id {
+        fields.push(("eprint", arxiv_id.clone()));
+        fields.push(("archiveprefix", String::from("arXiv")));
+    }
+    fields.push(("url", share.uri.clone()));
+    if !share.tags.is_empty() {
+        fields.push(("keywords", share.tags.join(", ")));
+    }
+    if let Some(abstract_text) = &share.abstract_text {
+        fields.push(("abstract", abstract_text.clone()));
+    }
+    if let Some(language) = &share.language {
+        fields.push(("language", language.clone()));
+    }
+
+    for (name, value) in fields {
+        let value = match name {
+            "url" | "doi" | "eprint" => escape_braces(&value),
+            _ => escape_latex(&value),
+        };
+        writeln!(w, "  {} = {{{}}},", name, value)?;
+    }
+    writeln!(w, "}}")?;
+
+    Ok(())
+}
+
+/// Writes `share` as a RIS record, using the post hash as its ID.
+fn write_ris(mut w: impl io::Write, hash: &str, share: &Share) -> Result<(), Error> {
+    let record_type = if share.venue.is_some() { "JOUR" } else { "GEN" };
+    writeln!(w, "TY  - {}", record_type)?;
+    writeln!(w, "ID  - {}", hash)?;
+    writeln!(w, "TI  - {}", ris_value(&share.title))?;
+    for author in &share.authors {
+        writeln!(w, "AU  - {}", ris_value(strip_email(author)))?;
+    }
+    if let Some(year) = share.year {
+        writeln!(w, "PY  - {}", year)?;
+    }
+    if let Some(venue) = &share.venue {
+        writeln!(w, "JO  - {}", ris_value(venue))?;
+    }
+    if let Some(doi) = &share.doi {
+        writeln!(w, "DO  - {}", ris_value(doi))?;
+    }
+    writeln!(w, "UR  - {}", ris_value(&share.uri))?;
+    for tag in &share.tags {
+        writeln!(w, "KW  - {}", ris_value(tag))?;
+    }
+    if let Some(abstract_text) = &share.abstract_text {
+        writeln!(w, "AB  - {}", ris_value(abstract_text))?;
+    }
+    if let Some(language) = &share.language {
+        writeln!(w, "LA  - {}", ris_value(language))?;
+    }
+    writeln!(w, "ER  - ")?;
+    writeln!(w)?;
+
+    Ok(())
+}
+
+/// Builds CSL-JSON item for `share`, using the post hash as its ID.
+fn csl_item(hash: &str, share: &Share) -> serde_json::Value {
+    let item_type = if share.venue.is_some() {
+        "article-journal"
+    } else {
+        "article"
+    };
+    let mut item = json!({
+        "id": hash,
+        "type": item_type,
+        "title": share.title,
+        "URL": share.uri,
+    });
+
+    let authors: Vec<_> = share
+        .authors
+        .iter()
+        .map(|a| match split_name(a) {
+            (Some(given), family) => json!({ "given": given, "family": family }),
+            (None, family) => json!({ "literal": family }),
+        })
+        .collect();
+    if !authors.is_empty() {
+        item["author"] = json!(authors);
+    }
+    if let Some(year) = share.year {
+        item["issued"] = json!({ "date-parts": [[year]] });
+    }
+    if let Some(venue) = &share.venue {
+        item["container-title"] = json!(venue);
+    }
+    if let Some(doi) = &share.doi {
+        item["DOI"] = json!(doi);
+    }
+    if !share.tags.is_empty() {
+        item["keyword"] = json!(share.tags.join(", "));
+    }
+    if let Some(abstract_text) = &share.abstract_text {
+        item["abstract"] = json!(abstract_text);
+    }
+    if let Some(language) = &share.language {
+        item["language"] = json!(language);
+    }
+
+    item
+}
+
+/// Escapes the LaTeX special characters of plain text `value`, so it is typeset as is. Braces
+/// are written as commands, so the field braces are always balanced.
+fn escape_latex(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' | '%' | '$' | '#' | '_' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '{' => escaped.push_str("\\textbraceleft{}"),
+            '}' => escaped.push_str("\\textbraceright{}"),
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Percent encodes the braces of URI `value`, which are not escaped as LaTeX, so the field braces
+/// are always balanced.
+fn escape_braces(value: &str) -> String {
+    value.replace('{', "%7B").replace('}', "%7D")
+}
+
+/// Collapses whitespace of `value`, such as line breaks, which would end a RIS field.
+fn ris_value(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Removes a trailing `<email>` from an author name.
+fn strip_email(author: &str) -> &str {
+    match author.split_once('<') {
+        Some((name, _)) => name.trim(),
+        None => author.trim(),
+    }
+}
+
+/// Splits author name into given and family names, the family name being the last word.
+fn split_name(author: &str) -> (Option<&str>, &str) {
+    let author = strip_email(author);
+    match author.rsplit_once(' ') {
+        Some((given, family)) => (Some(given.trim()), family),
+        None => (None, author),
+    }
+}
+
+/// Builds a citation key from first author family name, year and first title word, such as
+/// `doe2020freenet`.
+fn citation_key(share: &Share) -> String {
+    let author = share
+        .authors
+        .first()
+        .map(|a| split_name(a).1)
+        .unwrap_or("anon");
+    let year = share.year.map(|y| y.to_string()).unwrap_or_default();
+    let word = share
+        .title
+        .split_whitespace()
+        .find(|w| w.len() > 3)
+        .unwrap_or("");
+
+    format!("{}{}{}", author, year, word)
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Appends a letter suffix to `key`, as `a`, `b`, ..., `z`, `aa`, `ab`, ..., until it is not on
+/// `keys`, then adds it to `keys`.
+fn unique_key(keys: &mut HashSet<String>, key: String) -> String {
+    let mut unique = key.clone();
+    let mut n = 0;
+    while keys.contains(&unique) {
+        unique = format!("{}{}", key, letter_suffix(n));
+        n += 1;
+    }
+
+    keys.insert(unique.clone());
+    unique
+}
+
+/// Returns `n`-th letter suffix, counting from `a` for `0`.
+fn letter_suffix(mut n: usize) -> String {
+    let mut suffix = Vec::new();
+    loop {
+        suffix.push(b'a' + (n % 26) as u8);
+        if n < 26 {
+            break;
+        }
+        n = n / 26 - 1;
+    }
+    suffix.reverse();
+
+    String::from_utf8(suffix).expect("suffix is made of ASCII letters")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(hash: &str, share: Share) -> SearchResult {
+        SearchResult {
+            hash: String::from(hash),
+            reps: 2,
+            share,
+            snippet: None,
+        }
+    }
+
+    fn article() -> Share {
+        let mut share = Share::new(
+            "Freenet: A Distributed Anonymous Information Storage",
+            &["Ian Clarke <ian@example.site>", "Oskar Sandberg"],
+            &["p2p", "anonymity"],
+            "https://example.site/freenet.pdf",
+        );
+        share.year = Some(2001);
+        share.venue = Some(String::from("Designing Privacy"));
+        share.doi = Some(String::from("10.1007/3-540-44702-4_4"));
+        share.language = Some(String::from("en"));
+        share
+    }
+
+    fn written(format: Format, results: &[SearchResult]) -> String {
+        let mut w = Vec::new();
+        write(&mut w, format, results).unwrap();
+        String::from_utf8(w).unwrap()
+    }
+
+    #[test]
+    fn writes_hashes() {
+        let mut found = result("2_B", article());
+        found.snippet = Some(String::from("anonymous storage"));
+        let results = vec![result("1_A", article()), found];
+
+        assert_eq!(
+            written(Format::Hashes, &results),
+            "1_A 2\n2_B 2\n    anonymous storage\n"
+        );
+    }
+
+    #[test]
+    fn writes_json() {
+        let results = vec![result("1_A", article())];
+
+        let array: serde_json::Value =
+            serde_json::from_str(&written(Format::Json, &results)).unwrap();
+        assert_eq!(array[0]["hash"], "1_A");
+        assert_eq!(array[0]["reps"], 2);
+        assert_eq!(array[0]["year"], 2001);
+        assert!(array[0].get("snippet").is_none());
+
+        let lines = written(Format::JsonLines, &results);
+        assert_eq!(lines.lines().count(), 1);
+        let line: serde_json::Value = serde_json::from_str(&lines).unwrap();
+        assert_eq!(line, array[0]);
+    }
+
+    #[test]
+    fn writes_bibtex() {
+        let results = vec![result("1_A", article())];
+
+        assert_eq!(
+            written(Format::Bibtex, &results),
+            "@article{clarke2001freenet,
+  title = {Freenet: A Distributed Anonymous Information Storage},
+  author = {Ian Clarke and Oskar Sandberg},
+  year = {2001},
+  journal = {Designing Privacy},
+  doi = {10.1007/3-540-44702-4_4},
+  url = {https://example.site/freenet.pdf},
+  keywords = {p2p, anonymity},
+  language = {en},
+}
+"
+        );
+    }
+
+    #[test]
+    fn escapes_bibtex_values() {
+        let mut share = Share::new(
+            "Profit & {Loss} of 50% ~ \\o/",
+            &[],
+            &[],
+            "https://example.site/a_{b}",
+        );
+        share.arxiv_id = Some(String::from("2101.00001"));
+
+        let bibtex = written(Format::Bibtex, &[result("1_A", share)]);
+        assert!(bibtex.starts_with("@misc{anonprofit,\n"), "{}", bibtex);
+        assert!(bibtex.contains(
+            "title = {Profit \\& \\textbraceleft{}Loss\\textbraceright{} of 50\\% \
+             \\textasciitilde{} \\textbackslash{}o/},"
+        ));
+        assert!(bibtex.contains("eprint = {2101.00001},\n  archiveprefix = {arXiv},"));
+        assert!(bibtex.contains("url = {https://example.site/a_%7Bb%7D},"));
+    }
+
+    #[test]
+    fn writes_unique_bibtex_keys() {
+        let results: Vec<_> = (0..3).map(|_| result("1_A", article())).collect();
+
+        let keys: Vec<_> = written(Format::Bibtex, &results)
+            .lines()
+            .filter_map(|l| l.strip_prefix("@article{"))
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                "clarke2001freenet,",
+                "clarke2001freeneta,",
+                "clarke2001freenetb,"
+            ]
+        );
+    }
+
+    #[test]
+    fn suffixes_colliding_keys() {
+        let mut keys = HashSet::new();
+        let suffixed: Vec<_> = (0..30)
+            .map(|_| unique_key(&mut keys, String::from("key")))
+            .collect();
+
+        assert_eq!(suffixed[0], "key");
+        assert_eq!(suffixed[1], "keya");
+        assert_eq!(suffixed[26], "keyz");
+        assert_eq!(suffixed[27], "keyaa");
+        assert_eq!(suffixed[29], "keyac");
+        assert_eq!(keys.len(), 30);
+
+        let mut keys = HashSet::new();
+        assert_eq!(unique_key(&mut keys, String::from("keya")), "keya");
+        assert_eq!(unique_key(&mut keys, String::from("key")), "key");
+        assert_eq!(unique_key(&mut keys, String::from("key")), "keyb");
+    }
+
+    #[test]
+    fn counts_letter_suffixes() {
+        assert_eq!(letter_suffix(0), "a");
+        assert_eq!(letter_suffix(25), "z");
+        assert_eq!(letter_suffix(26), "aa");
+        assert_eq!(letter_suffix(26 + 26 * 26 - 1), "zz");
+        assert_eq!(letter_suffix(26 + 26 * 26), "aaa");
+    }
+
+    #[test]
+    fn writes_ris() {
+        let mut share = article();
+        share.abstract_text = Some(String::from("First line,\nsecond line."));
+
+        assert_eq!(
+            written(Format::Ris, &[result("1_A", share)]),
+            "TY  - JOUR
+ID  - 1_A
+TI  - Freenet: A Distributed Anonymous Information Storage
+AU  - Ian Clarke
+AU  - Oskar Sandberg
+PY  - 2001
+JO  - Designing Privacy
+DO  - 10.1007/3-540-44702-4_4
+UR  - https://example.site/freenet.pdf
+KW  - p2p
+KW  - anonymity
+AB  - First line, second line.
+LA  - en
+ER  - 
+
+"
+        );
+    }
+
+    #[test]
+    fn writes_csl_json() {
+        let mut share = Share::new("Paper", &["Plato", "Ian Clarke"], &[], "x");
+        share.year = Some(2001);
+        let results = vec![result("1_A", article()), result("2_B", share)];
+
+        let items: serde_json::Value =
+            serde_json::from_str(&written(Format::CslJson, &results)).unwrap();
+        assert_eq!(items[0]["id"], "1_A");
+        assert_eq!(items[0]["type"], "article-journal");
+        assert_eq!(items[0]["container-title"], "Designing Privacy");
+        assert_eq!(items[0]["DOI"], "10.1007/3-540-44702-4_4");
+        assert_eq!(items[0]["keyword"], "p2p, anonymity");
+        assert_eq!(items[1]["type"], "article");
+        assert_eq!(
+            items[1]["author"],
+            json!([{ "literal": "Plato" }, { "given": "Ian", "family": "Clarke" }])
+        );
+        assert_eq!(items[1]["issued"], json!({ "date-parts": [[2001]] }));
+    }
+}
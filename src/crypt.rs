@@ -0,0 +1,429 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use seresa::Error::InputError;
+use sha2::{Digest, Sha256, Sha512};
+
+use std::convert::TryInto;
+use std::fmt;
+
+/// Length of the authentication tag appended to each encrypted block, in bytes.
+pub const TAG_SIZE: usize = 16;
+
+/// Length of hex encoded block nonces.
+pub const NONCE_LEN: usize = 24;
+
+/// Largest Argon2 memory cost accepted from a resource, in KiB.
+const MAX_M_COST: u32 = 1024 * 1024;
+
+/// Key derivation header of an encrypted resource, present on each of its posts.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Encryption {
+    /// Authenticated cipher of the blocks contents.
+    pub cipher: Cipher,
+
+    /// Derivation of the cipher key.
+    pub kdf: Kdf,
+}
+
+/// Authenticated cipher of encrypted resources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Cipher {
+    /// [ChaCha20-Poly1305](https://www.rfc-editor.org/rfc/rfc8439), with a random nonce per block.
+    #[serde(rename = "chacha20poly1305")]
+    ChaCha20Poly1305,
+}
+
+/// Key derivation function of encrypted resources.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "name", rename_all = "lowercase")]
+pub enum Kdf {
+    /// Argon2id of a passphrase, with hex encoded `salt`.
+    Argon2id {
+        salt: String,
+        m_cost: u32,
+        t_cost: u32,
+        p_cost: u32,
+    },
+
+    /// HKDF-SHA256 of the X25519 shared secret of a hex encoded `ephemeral` key and the
+    /// `recipient` Freechains public key.
+    X25519 {
+        recipient: String,
+        ephemeral: String,
+    },
+}
+
+/// Key used to encrypt a resource.
+pub enum EncryptionKey<'a> {
+    /// Passphrase shared with the readers.
+    Passphrase(&'a str),
+
+    /// Freechains public key of the only reader.
+    Recipient(&'a str),
+}
+
+impl EncryptionKey<'_> {
+    /// Returns name of the key kind.
+    pub fn name(&self) -> &'static str {
+        match self {
+            EncryptionKey::Passphrase(_) => "passphrase",
+            EncryptionKey::Recipient(_) => "recipient",
+        }
+    }
+}
+
+/// Key used to decrypt a resource.
+pub enum DecryptionKey<'a> {
+    /// Passphrase the resource was encrypted with.
+    Passphrase(&'a str),
+
+    /// Freechains private key of the resource recipient.
+    PrivateKey(&'a str),
+}
+
+/// Cipher of the blocks of a resource, with its derived key, which is never stored.
+pub struct BlockCipher {
+    encryption: Encryption,
+    key: [u8; 32],
+}
+
+impl fmt::Debug for BlockCipher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BlockCipher")
+            .field("encryption", &self.encryption)
+            .finish_non_exhaustive()
+    }
+}
+
+impl BlockCipher {
+    /// Creates cipher of a new resource, with a random salt or ephemeral key.
+    pub fn new(key: &EncryptionKey) -> Result<BlockCipher, seresa::Error> {
+        let (kdf, key) = match key {
+            EncryptionKey::Passphrase(passphrase) => {
+                let mut salt = [0; 16];
+                OsRng.fill_bytes(&mut salt);
+
+                let kdf = Kdf::Argon2id {
+                    salt: hex::encode(salt),
+                    m_cost: Params::DEFAULT_M_COST,
+                    t_cost: Params::DEFAULT_T_COST,
+                    p_cost: Params::DEFAULT_P_COST,
+                };
+                let key = derive_passphrase(&kdf, passphrase)?;
+                (kdf, key)
+            }
+            EncryptionKey::Recipient(public_key) => {
+                let recipient = parse_public_key(public_key)?;
+
+                let mut secret = [0; 32];
+                OsRng.fill_bytes(&mut secret);
+                let ephemeral = MontgomeryPoint::mul_base_clamped(secret);
+
+                let key = derive_shared(recipient.mul_clamped(secret), ephemeral, recipient)?;
+                let kdf = Kdf::X25519 {
+                    recipient: public_key.to_uppercase(),
+                    ephemeral: hex::encode(ephemeral.as_bytes()),
+                };
+                (kdf, key)
+            }
+        };
+
+        Ok(BlockCipher {
+            encryption: Encryption {
+                cipher: Cipher::ChaCha20Poly1305,
+                kdf,
+            },
+            key,
+        })
+    }
+
+    /// Opens cipher of a resource encrypted as described by `encryption`. Keys of another kind
+    /// than the resource's are refused, while wrong keys of the same kind are only noticed when a
+    /// block is decrypted.
+    pub fn open(
+        encryption: &Encryption,
+        key: &DecryptionKey,
+    ) -> Result<BlockCipher, seresa::Error> {
+        let key = match (&encryption.kdf, key) {
+            (Kdf::Argon2id { .. }, DecryptionKey::Passphrase(passphrase)) => {
+                derive_passphrase(&encryption.kdf, passphrase)?
+            }
+            (
+                Kdf::X25519 {
+                    recipient,
+                    ephemeral,
+                },
+                DecryptionKey::PrivateKey(private_key),
+            ) => {
+                let (public_key, secret) = parse_private_key(private_key)?;
+                if !public_key.eq_ignore_ascii_case(recipient) {
+                    return Err(InputError(format!(
+                        "resource is encrypted to public key \"{}\", not to the given private key",
+                        recipient
+                    )));
+                }
+
+                let recipient = parse_public_key(recipient)?;
+                let ephemeral = parse_point(ephemeral)
+                    .ok_or_else(|| InputError(String::from("invalid resource ephemeral key")))?;
+                derive_shared(ephemeral.mul_clamped(secret), ephemeral, recipient)?
+            }
+            (Kdf::Argon2id { .. }, DecryptionKey::PrivateKey(_)) => {
+                return Err(InputError(String::from(
+                    "resource is encrypted with a passphrase, not to a public key",
+                )))
+            }
+            (Kdf::X25519 { .. }, DecryptionKey::Passphrase(_)) => {
+                return Err(InputError(String::from(
+                    "resource is encrypted to a public key, not with a passphrase",
+                )))
+            }
+        };
+
+        Ok(BlockCipher {
+            encryption: encryption.clone(),
+            key,
+        })
+    }
+
+    /// Returns the key derivation header of the resource.
+    pub fn encryption(&self) -> &Encryption {
+        &self.encryption
+    }
+
+    /// Encrypts `content`, returning the hex encoded random nonce and the encrypted content, which
+    /// is [TAG_SIZE] bytes longer.
+    pub fn encrypt(&self, content: &[u8]) -> (String, Vec<u8>) {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let encrypted = ChaCha20Poly1305::new(&self.key.into())
+            .encrypt(&nonce, content)
+            .expect("block content fits on a ChaCha20-Poly1305 message");
+
+        (hex::encode(nonce), encrypted)
+    }
+
+    /// Decrypts `content` with hex encoded `nonce`, [None] if the key is wrong or the content was
+    /// tampered with.
+    pub fn decrypt(&self, nonce: &str, content: &[u8]) -> Option<Vec<u8>> {
+        let nonce = hex::decode(nonce)
+            .ok()
+            .filter(|n| n.len() == NONCE_LEN / 2)?;
+        ChaCha20Poly1305::new(&self.key.into())
+            .decrypt(Nonce::from_slice(&nonce), content)
+            .ok()
+    }
+}
+
+/// Derives key of a passphrase with Argon2id `kdf`.
+fn derive_passphrase(kdf: &Kdf, passphrase: &str) -> Result<[u8; 32], seresa::Error> {
+    let invalid = || InputError(String::from("invalid resource key derivation parameters"));
+
+    let (salt, m_cost, t_cost, p_cost) = match kdf {
+        Kdf::Argon2id {
+            salt,
+            m_cost,
+            t_cost,
+            p_cost,
+        } => (salt, *m_cost, *t_cost, *p_cost),
+        _ => return Err(invalid()),
+    };
+    if m_cost > MAX_M_COST {
+        return Err(invalid());
+    }
+
+    let salt = hex::decode(salt).map_err(|_| invalid())?;
+    let params = Params::new(m_cost, t_cost, p_cost, Some(32)).map_err(|_| invalid())?;
+
+    let mut key = [0; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|_| invalid())?;
+    Ok(key)
+}
+
+/// Derives key of X25519 `shared` secret between `ephemeral` and `recipient` keys.
+fn derive_shared(
+    shared: MontgomeryPoint,
+    ephemeral: MontgomeryPoint,
+    recipient: MontgomeryPoint,
+) -> Result<[u8; 32], seresa::Error> {
+    // Low order points give a known shared secret.
+    if shared.as_bytes() == &[0; 32] {
+        return Err(InputError(String::from("invalid X25519 key")));
+    }
+
+    let salt = [ephemeral.as_bytes().as_ref(), recipient.as_bytes()].concat();
+    let mut key = [0; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes())
+        .expand(b"seresa resource", &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    Ok(key)
+}
+
+/// Parses hex encoded X25519 point.
+fn parse_point(point: &str) -> Option<MontgomeryPoint> {
+    let bytes = hex::decode(point).ok()?;
+    Some(MontgomeryPoint(bytes.try_into().ok()?))
+}
+
+/// Parses hex encoded Freechains (Ed25519) public key, as its X25519 point.
+fn parse_public_key(public_key: &str) -> Result<MontgomeryPoint, seresa::Error> {
+    hex::decode(public_key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .and_then(|bytes| CompressedEdwardsY(bytes).decompress())
+        .map(|point| point.to_montgomery())
+        .ok_or_else(|| InputError(format!("invalid public key \"{}\"", public_key)))
+}
+
+/// Parses hex encoded Freechains (Ed25519) private key, returning its hex encoded public key and
+/// its X25519 secret.
+fn parse_private_key(private_key: &str) -> Result<(String, [u8; 32]), seresa::Error> {
+    let bytes = hex::decode(private_key)
+        .ok()
+        .filter(|b| b.len() == 64)
+        .ok_or_else(|| InputError(String::from("invalid private key")))?;
+
+    // Ed25519 private keys are the seed followed by the public key.
+    let mut secret = [0; 32];
+    secret.copy_from_slice(&Sha512::digest(&bytes[..32])[..32]);
+
+    Ok((hex::encode(&bytes[32..]), secret))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Freechains keypair, as returned by `freechains crypto pubpvt`.
+    const PUBLIC_KEY: &str = "54898902E7F3442A16E2F6CBBE2D0B24AD30A1CB964AFDEFB2E6CD08AE5C3DCF";
+    const PRIVATE_KEY: &str = "C878D344A09447145AD01DD6E0DF1ECED89456BEDE03E6CA7F575A2358C7D807\
+                               54898902E7F3442A16E2F6CBBE2D0B24AD30A1CB964AFDEFB2E6CD08AE5C3DCF";
+    const OTHER_PRIVATE_KEY: &str = "2E85E31B55B01EEE75D916B972CB9390509056048145090F8D6308F148A2DF55\
+                                     B184B604D66B29524A0BAC9359FF40C1F670BA01D014726F9B9225CBF9C549B8";
+
+    /// Cheap Argon2id parameters, so tests run fast.
+    fn argon2id(m_cost: u32) -> Encryption {
+        Encryption {
+            cipher: Cipher::ChaCha20Poly1305,
+            kdf: Kdf::Argon2id {
+                salt: String::from("00112233445566778899aabbccddeeff"),
+                m_cost,
+                t_cost: 1,
+                p_cost: 1,
+            },
+        }
+    }
+
+    #[test]
+    fn encrypts_with_passphrase() {
+        let cipher = BlockCipher::new(&EncryptionKey::Passphrase("secret")).unwrap();
+        let (nonce, encrypted) = cipher.encrypt(b"block content");
+        assert_eq!(nonce.len(), NONCE_LEN);
+        assert_eq!(encrypted.len(), b"block content".len() + TAG_SIZE);
+
+        let opened =
+            BlockCipher::open(cipher.encryption(), &DecryptionKey::Passphrase("secret")).unwrap();
+        assert_eq!(
+            opened.decrypt(&nonce, &encrypted).as_deref(),
+            Some(&b"block content"[..])
+        );
+    }
+
+    #[test]
+    fn refuses_wrong_passphrase() {
+        let encryption = argon2id(8);
+        let cipher = BlockCipher::open(&encryption, &DecryptionKey::Passphrase("secret")).unwrap();
+        let (nonce, encrypted) = cipher.encrypt(b"block content");
+
+        let wrong = BlockCipher::open(&encryption, &DecryptionKey::Passphrase("guess")).unwrap();
+        assert_eq!(wrong.decrypt(&nonce, &encrypted), None);
+    }
+
+    #[test]
+    fn refuses_tampered_blocks() {
+        let cipher = BlockCipher::open(&argon2id(8), &DecryptionKey::Passphrase("secret")).unwrap();
+        let (nonce, mut encrypted) = cipher.encrypt(b"block content");
+        let (other_nonce, _) = cipher.encrypt(b"block content");
+
+        assert_eq!(cipher.decrypt(&other_nonce, &encrypted), None);
+        assert_eq!(cipher.decrypt(&nonce[2..], &encrypted), None);
+        assert_eq!(cipher.decrypt("not hex", &encrypted), None);
+
+        encrypted[0] ^= 1;
+        assert_eq!(cipher.decrypt(&nonce, &encrypted), None);
+    }
+
+    #[test]
+    fn encrypts_to_recipient() {
+        let cipher = BlockCipher::new(&EncryptionKey::Recipient(PUBLIC_KEY)).unwrap();
+        let (nonce, encrypted) = cipher.encrypt(b"block content");
+
+        let opened =
+            BlockCipher::open(cipher.encryption(), &DecryptionKey::PrivateKey(PRIVATE_KEY))
+                .unwrap();
+        assert_eq!(
+            opened.decrypt(&nonce, &encrypted).as_deref(),
+            Some(&b"block content"[..])
+        );
+
+        let lowercase = PRIVATE_KEY.to_lowercase();
+        let opened =
+            BlockCipher::open(cipher.encryption(), &DecryptionKey::PrivateKey(&lowercase)).unwrap();
+        assert!(opened.decrypt(&nonce, &encrypted).is_some());
+    }
+
+    #[test]
+    fn refuses_other_private_key() {
+        let cipher = BlockCipher::new(&EncryptionKey::Recipient(PUBLIC_KEY)).unwrap();
+
+        let err = BlockCipher::open(
+            cipher.encryption(),
+            &DecryptionKey::PrivateKey(OTHER_PRIVATE_KEY),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains(PUBLIC_KEY), "{}", err);
+    }
+
+    #[test]
+    fn refuses_key_of_other_kind() {
+        let recipient = BlockCipher::new(&EncryptionKey::Recipient(PUBLIC_KEY)).unwrap();
+        let err = BlockCipher::open(recipient.encryption(), &DecryptionKey::Passphrase("secret"))
+            .unwrap_err();
+        assert!(matches!(err, InputError(_)));
+        assert!(err.to_string().contains("not with a passphrase"), "{}", err);
+
+        let err =
+            BlockCipher::open(&argon2id(8), &DecryptionKey::PrivateKey(PRIVATE_KEY)).unwrap_err();
+        assert!(matches!(err, InputError(_)));
+        assert!(err.to_string().contains("not to a public key"), "{}", err);
+    }
+
+    #[test]
+    fn refuses_expensive_key_derivation() {
+        let kdf = argon2id(MAX_M_COST + 1).kdf;
+        assert!(matches!(
+            derive_passphrase(&kdf, "secret"),
+            Err(InputError(_))
+        ));
+    }
+
+    #[test]
+    fn refuses_invalid_keys() {
+        assert!(BlockCipher::new(&EncryptionKey::Recipient("not hex")).is_err());
+        assert!(BlockCipher::new(&EncryptionKey::Recipient(&PUBLIC_KEY[2..])).is_err());
+
+        let recipient = BlockCipher::new(&EncryptionKey::Recipient(PUBLIC_KEY)).unwrap();
+        assert!(BlockCipher::open(
+            recipient.encryption(),
+            &DecryptionKey::PrivateKey(&PRIVATE_KEY[..64])
+        )
+        .is_err());
+    }
+}
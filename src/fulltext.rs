@@ -0,0 +1,68 @@
+//! Text extraction and matching of shared resources contents.
+
+use std::panic;
+
+/// Number of characters around a match shown on snippets.
+const SNIPPET_CONTEXT: usize = 60;
+
+/// Largest resource whose text is extracted, in bytes, since resources are extracted in memory.
+pub const MAX_RESOURCE_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Checks if text can be extracted from resources of MIME type `mime`, which are plain text and
+/// PDF files.
+pub fn is_supported(mime: &str) -> bool {
+    mime == "application/pdf" || mime.starts_with("text/")
+}
+
+/// Extracts text from resource contents. Plain text and PDF files are supported, returns [None]
+/// for other formats or if extraction fails.
+///
+/// Whitespace is collapsed on the returned text.
+pub fn extract_text(content: &[u8]) -> Option<String> {
+    let text = if content.starts_with(b"%PDF") {
+        // Malformed PDFs may panic the extractor.
+        panic::catch_unwind(|| pdf_extract::extract_text_from_mem(content))
+            .ok()?
+            .ok()?
+    } else {
+        String::from(std::str::from_utf8(content).ok()?)
+    };
+
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+/// Finds `pattern` on `text`, ignoring case, returning a snippet of the text around the first
+/// match.
+pub fn find_snippet(text: &str, pattern: &str) -> Option<String> {
+    let pattern: Vec<char> = pattern.chars().collect();
+    if pattern.is_empty() {
+        return None;
+    }
+
+    let chars: Vec<char> = text.chars().collect();
+    let start = chars.windows(pattern.len()).position(|w| {
+        w.iter()
+            .zip(&pattern)
+            .all(|(a, b)| a.to_lowercase().eq(b.to_lowercase()))
+    })?;
+    let end = start + pattern.len();
+
+    let from = start.saturating_sub(SNIPPET_CONTEXT);
+    let to = (end + SNIPPET_CONTEXT).min(chars.len());
+
+    let mut snippet = String::new();
+    if from > 0 {
+        snippet.push_str("...");
+    }
+    snippet.extend(&chars[from..to]);
+    if to < chars.len() {
+        snippet.push_str("...");
+    }
+
+    Some(snippet)
+}
@@ -0,0 +1,461 @@
+//! Persistent local index of share forum posts.
+//!
+//! Each index belongs to a chain of a Freechains node, and records the share of every post on the
+//! chain consensus, so only new posts payloads have to be fetched from the node on later searches.
+//!
+//! Optionally, the text of resources referenced by `fchs` URIs is also indexed, to be used by full
+//! text searches. Texts are stored on their own files, next to the index.
+
+use crate::{fulltext, Error, Share};
+use freechains::{ChainClient, ClientError, Connect};
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Function fetching resource contents, given the resource URI. Returns [None], without fetching
+/// the resource, if its text cannot be indexed, such as for resources of unsupported types.
+pub type FetchResource<'a> = dyn FnMut(&str) -> Result<Option<Vec<u8>>, Error> + 'a;
+
+/// Indexed post.
+#[derive(Debug, Deserialize, Serialize)]
+struct Entry {
+    /// Post share, [None] if post is not a share.
+    share: Option<Share>,
+
+    /// Last known post reputation.
+    reps: isize,
+
+    /// If referenced resource text was already indexed, or could not be indexed.
+    #[serde(default)]
+    text_indexed: bool,
+}
+
+/// Local index of a share forum.
+#[derive(Debug)]
+pub struct Index {
+    path: PathBuf,
+    text_dir: PathBuf,
+    data: Data,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Data {
+    /// Chain consensus, on the last update.
+    consensus: Vec<String>,
+
+    /// Indexed posts, by hash.
+    posts: HashMap<String, Entry>,
+}
+
+/// Default directory for indexes, inside user's cache directory.
+pub fn default_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|d| d.join("seresa").join("index"))
+}
+
+impl Index {
+    /// Opens index of `chain` from Freechains `node` stored on `dir`. If there is no index stored,
+    /// an empty index is returned.
+    pub fn open(dir: &Path, node: &str, chain: &str) -> Result<Index, Error> {
+        let dir = dir.join(escape_filename(node));
+        let path = dir.join(format!("{}.json", escape_filename(chain)));
+        let text_dir = dir.join(format!("{}.text", escape_filename(chain)));
+
+        let data = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Data::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Index {
+            path,
+            text_dir,
+            data,
+        })
+    }
+
+    /// Writes index to disk.
+    pub fn save(&self) -> Result<(), Error> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec(&self.data)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        Ok(())
+    }
+
+    /// Removes every indexed post and text.
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.data = Data::default();
+
+        match fs::remove_dir_all(&self.text_dir) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Updates index with chain consensus, fetching only posts which are not indexed yet.
+    ///
+    /// Posts which left the consensus are removed. Returns the number of fetched posts.
+    pub fn update<T>(&mut self, chain_client: &ChainClient<T>) -> Result<usize, Error>
+    where
+        T: Connect,
+    {
+        let consensus = chain_client.consensus()?;
+
+        let mut fetched = 0;
+        for hash in &consensus {
+            if self.data.posts.contains_key(hash) {
+                continue;
+            }
+
+            let payload = chain_client.payload(hash, None)?;
+            let (share, reps) = match Share::from_payload(&payload) {
+                Ok(share) => (Some(share), chain_client.reputation(hash)?),
+                Err(_) => (None, 0),
+            };
+            let entry = Entry {
+                share,
+                reps,
+                text_indexed: false,
+            };
+            self.data.posts.insert(hash.clone(), entry);
+            fetched += 1;
+        }
+
+        let mut posts = std::mem::take(&mut self.data.posts);
+        self.data.posts = consensus
+            .iter()
+            .filter_map(|h| posts.remove_entry(h))
+            .collect();
+        self.data.consensus = consensus;
+
+        Ok(fetched)
+    }
+
+    /// Indexes the text of resources referenced by `fchs` URIs on shares, which were not indexed
+    /// yet. Resources contents are fetched by `fetch`, given the resource URI.
+    ///
+    /// Resources skipped by `fetch`, with invalid contents or without text, or which cannot be
+    /// fetched, because of their URI or of their blocks reputation, will not be fetched again
+    /// until the index is rebuilt. Resources which cannot be fetched because of the node are
+    /// skipped until the next index update. Returns the number of indexed texts.
+    pub fn index_texts(&mut self, fetch: &mut FetchResource) -> Result<usize, Error> {
+        fs::create_dir_all(&self.text_dir)?;
+
+        let mut indexed = 0;
+        for (hash, entry) in self.data.posts.iter_mut() {
+            let uri = match &entry.share {
+                Some(share) if !entry.text_indexed && share.uri.starts_with("fchs:") => &share.uri,
+                _ => continue,
+            };
+
+            let text = match fetch(uri) {
+                Ok(Some(content)) => fulltext::extract_text(&content),
+                Ok(None) => None,
+                Err(e @ Error::IoError(_))
+                | Err(e @ Error::FreechainsError(ClientError::IoError(_))) => return Err(e),
+                Err(Error::FreechainsError(_)) => continue,
+                Err(_) => None,
+            };
+            if let Some(text) = text {
+                fs::write(self.text_dir.join(hash), text)?;
+                indexed += 1;
+            }
+            entry.text_indexed = true;
+        }
+
+        Ok(indexed)
+    }
+
+    /// Returns indexed text of resource referenced by share `hash`, if any.
+    pub fn text(&self, hash: &str) -> Result<Option<String>, Error> {
+        match fs::read_to_string(self.text_dir.join(hash)) {
+            Ok(text) => Ok(Some(text)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Returns indexed shares, on consensus order, with their hash and last known reputation.
+    pub fn shares(&self) -> impl Iterator<Item = (&str, &Share, isize)> {
+        self.data.consensus.iter().filter_map(move |hash| {
+            let entry = self.data.posts.get(hash)?;
+            let share = entry.share.as_ref()?;
+            Some((hash.as_str(), share, entry.reps))
+        })
+    }
+
+    /// Records post reputation.
+    pub fn set_reps(&mut self, hash: &str, reps: isize) {
+        if let Some(entry) = self.data.posts.get_mut(hash) {
+            entry.reps = reps;
+        }
+    }
+
+    /// Returns number of indexed shares.
+    pub fn len(&self) -> usize {
+        self.shares().count()
+    }
+
+    /// Checks if there are no indexed shares.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Escapes a node address or chain name, such as `#forum`, to be used as a filename.
+pub(crate) fn escape_filename(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c.to_string()
+            } else {
+                format!("_{:02x}", c as u32)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use freechains::{ChainId, Client, ReadWrite};
+
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+    use std::io::{Read, Write};
+    use std::process;
+    use std::rc::Rc;
+
+    /// Posts of a fake chain, by hash, with their payload and reputation, on consensus order.
+    type Posts = Rc<RefCell<BTreeMap<String, (Vec<u8>, isize)>>>;
+
+    /// Connector to a fake Freechains node, answering chain requests from `posts`, and counting
+    /// fetched payloads.
+    #[derive(Debug, Default)]
+    struct NodeMock {
+        posts: Posts,
+        payloads: Rc<RefCell<usize>>,
+    }
+
+    #[derive(Debug)]
+    struct ConnectionMock {
+        posts: Posts,
+        payloads: Rc<RefCell<usize>>,
+        request: Vec<u8>,
+        response: Option<io::Cursor<Vec<u8>>>,
+    }
+
+    impl Connect for NodeMock {
+        fn connect(&self) -> io::Result<Box<dyn ReadWrite>> {
+            Ok(Box::new(ConnectionMock {
+                posts: Rc::clone(&self.posts),
+                payloads: Rc::clone(&self.payloads),
+                request: Vec::new(),
+                response: None,
+            }))
+        }
+    }
+
+    impl ConnectionMock {
+        fn respond(&self) -> Vec<u8> {
+            let request = String::from_utf8_lossy(&self.request);
+            let words: Vec<_> = request.split_whitespace().skip(4).collect();
+            let posts = self.posts.borrow();
+            match words.as_slice() {
+                ["consensus"] => {
+                    let hashes: Vec<_> = posts.keys().map(String::as_str).collect();
+                    format!("{}\n", hashes.join(" ")).into_bytes()
+                }
+                ["get", "payload", hash, _] => {
+                    *self.payloads.borrow_mut() += 1;
+                    let mut response = format!("{}\n", posts[*hash].0.len()).into_bytes();
+                    response.extend(&posts[*hash].0);
+                    response
+                }
+                ["reps", hash] => format!("{}\n", posts[*hash].1).into_bytes(),
+                _ => panic!("unexpected request {:?}", request),
+            }
+        }
+    }
+
+    impl ReadWrite for ConnectionMock {}
+
+    impl Read for ConnectionMock {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.response.is_none() {
+                self.response = Some(io::Cursor::new(self.respond()));
+            }
+            self.response.as_mut().unwrap().read(buf)
+        }
+    }
+
+    impl Write for ConnectionMock {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.request.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("seresa-index-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn share_payload(title: &str, uri: &str) -> Vec<u8> {
+        serde_json::to_vec(&Share::new(title, &["Ian Clarke"], &[], uri)).unwrap()
+    }
+
+    #[test]
+    fn updates_from_consensus() {
+        let node = NodeMock::default();
+        let posts = Rc::clone(&node.posts);
+        let payloads = Rc::clone(&node.payloads);
+        posts
+            .borrow_mut()
+            .insert(String::from("1_A"), (share_payload("First", "x"), 2));
+        posts
+            .borrow_mut()
+            .insert(String::from("2_B"), (b"not a share".to_vec(), 0));
+        let client = Client::new(node);
+        let chain_client = client.chain(&ChainId::new("#forum").unwrap());
+
+        let dir = test_dir("update");
+        let mut index = Index::open(&dir, "localhost:8330", "#forum").unwrap();
+        assert!(index.is_empty());
+        assert_eq!(index.update(&chain_client).unwrap(), 2);
+        index.save().unwrap();
+
+        posts
+            .borrow_mut()
+            .insert(String::from("3_C"), (share_payload("Third", "y"), -1));
+        posts.borrow_mut().remove("1_A");
+
+        let mut index = Index::open(&dir, "localhost:8330", "#forum").unwrap();
+        assert_eq!(index.len(), 1);
+        assert_eq!(index.update(&chain_client).unwrap(), 1);
+        assert_eq!(*payloads.borrow(), 3);
+
+        let shares: Vec<_> = index
+            .shares()
+            .map(|(hash, share, reps)| (hash, share.title.as_str(), reps))
+            .collect();
+        assert_eq!(shares, vec![("3_C", "Third", -1)]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn records_reputation() {
+        let node = NodeMock::default();
+        node.posts
+            .borrow_mut()
+            .insert(String::from("1_A"), (share_payload("First", "x"), 2));
+        let client = Client::new(node);
+        let chain_client = client.chain(&ChainId::new("#forum").unwrap());
+
+        let mut index = Index::open(&test_dir("reps"), "localhost:8330", "#forum").unwrap();
+        index.update(&chain_client).unwrap();
+        index.set_reps("1_A", 7);
+        index.set_reps("9_Z", 1);
+
+        let reps: Vec<_> = index.shares().map(|(hash, _, reps)| (hash, reps)).collect();
+        assert_eq!(reps, vec![("1_A", 7)]);
+    }
+
+    #[test]
+    fn clears_posts_and_texts() {
+        let node = NodeMock::default();
+        node.posts.borrow_mut().insert(
+            String::from("1_A"),
+            (share_payload("First", "fchs:#forum:1_R"), 2),
+        );
+        let client = Client::new(node);
+        let chain_client = client.chain(&ChainId::new("#forum").unwrap());
+
+        let dir = test_dir("clear");
+        let mut index = Index::open(&dir, "localhost:8330", "#forum").unwrap();
+        index.update(&chain_client).unwrap();
+        let indexed = index
+            .index_texts(&mut |_| Ok(Some(b"resource text".to_vec())))
+            .unwrap();
+        assert_eq!(indexed, 1);
+        assert_eq!(index.text("1_A").unwrap().as_deref(), Some("resource text"));
+
+        index.clear().unwrap();
+        assert!(index.is_empty());
+        assert_eq!(index.text("1_A").unwrap(), None);
+        index.clear().unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn skips_resources_which_cannot_be_indexed() {
+        let node = NodeMock::default();
+        for (hash, uri) in &[
+            ("1_A", "fchs:1_R"),
+            ("2_B", "fchs:2_R"),
+            ("3_C", "fchs:3_R"),
+            ("4_D", "fchs:4_R"),
+            ("5_E", "https://example.site/paper.pdf"),
+        ] {
+            node.posts
+                .borrow_mut()
+                .insert(String::from(*hash), (share_payload("Title", uri), 0));
+        }
+        let client = Client::new(node);
+        let chain_client = client.chain(&ChainId::new("#forum").unwrap());
+
+        let dir = test_dir("skip");
+        let mut index = Index::open(&dir, "localhost:8330", "#forum").unwrap();
+        index.update(&chain_client).unwrap();
+
+        let mut fetched = Vec::new();
+        let mut fetch = |uri: &str| {
+            fetched.push(String::from(uri));
+            match uri {
+                "fchs:1_R" => Ok(Some(b"text".to_vec())),
+                "fchs:2_R" => Ok(None),
+                "fchs:3_R" => Err(Error::LowReputationError(
+                    String::from("#forum"),
+                    String::from("3_R"),
+                )),
+                _ => Err(Error::FreechainsError(ClientError::EmptyResponseError)),
+            }
+        };
+        assert_eq!(index.index_texts(&mut fetch).unwrap(), 1);
+        assert_eq!(index.index_texts(&mut fetch).unwrap(), 0);
+
+        fetched.sort();
+        assert_eq!(
+            fetched,
+            vec!["fchs:1_R", "fchs:2_R", "fchs:3_R", "fchs:4_R", "fchs:4_R"]
+        );
+        assert_eq!(index.text("1_A").unwrap().as_deref(), Some("text"));
+        assert_eq!(index.text("2_B").unwrap(), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn escapes_filenames() {
+        assert_eq!(escape_filename("localhost:8330"), "localhost_3a8330");
+        assert_eq!(escape_filename("#forum"), "_23forum");
+        assert_eq!(escape_filename("$private"), "_24private");
+        assert_eq!(escape_filename("@7A1F"), "_407A1F");
+        assert_eq!(escape_filename("@!7A1F"), "_40_217A1F");
+        assert_eq!(escape_filename("#a/b"), "_23a_2fb");
+        assert_ne!(escape_filename("#forum"), escape_filename("$forum"));
+    }
+}
@@ -0,0 +1,236 @@
+//! Local journal of resource uploads.
+//!
+//! Each journal belongs to a file upload to a chain of a Freechains node, and records the blocks
+//! and manifests already posted, so an interrupted upload can be resumed without posting them again.
+
+use crate::index::escape_filename;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Upload journal of a file.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    data: Data,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Data {
+    /// Format of the posted blocks, such as their size and compression, described by the uploader.
+    #[serde(default)]
+    format: String,
+
+    /// Hashes of the posted blocks, in order.
+    blocks: Vec<String>,
+
+    /// Encryption header of the posted blocks, without its key, described by the uploader, so
+    /// the next blocks are encrypted with the same key, derived again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encryption: Option<String>,
+
+    /// Hash of the posted manifest, [None] if not posted yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    manifest: Option<String>,
+}
+
+/// Default directory for journals, inside user's local data directory.
+pub fn default_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("seresa").join("uploads"))
+}
+
+/// Returns hex encoded SHA-256 digest of file at `path`, which journals of its uploads are found
+/// by.
+pub fn file_digest(path: &Path) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+impl Journal {
+    /// Opens journal of the upload of a file to `chain` from Freechains `node`, stored on `dir`.
+    /// Journals are found by the file SHA-256 `digest`, as returned by [file_digest], so renamed
+    /// files are still resumed.
+    ///
+    /// If there is no journal stored, an empty journal is returned.
+    pub fn open(dir: &Path, node: &str, chain: &str, digest: &str) -> Result<Journal, Error> {
+        let path = dir
+            .join(escape_filename(node))
+            .join(escape_filename(chain))
+            .join(format!("{}.json", digest));
+
+        let data = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Data::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Journal { path, data })
+    }
+
+    /// Returns hashes of the posted blocks, in order.
+    pub fn blocks(&self) -> &[String] {
+        &self.data.blocks
+    }
+
+    /// Returns format of the posted blocks, [None] if no block was posted. Blocks of another format
+    /// cannot be resumed.
+    pub fn format(&self) -> Option<&str> {
+        if self.data.blocks.is_empty() {
+            None
+        } else {
+            Some(&self.data.format)
+        }
+    }
+
+    /// Returns encryption of the posted blocks, [None] if no block was posted or if they are not
+    /// encrypted.
+    pub fn encryption(&self) -> Option<&str> {
+        if self.data.blocks.is_empty() {
+            None
+        } else {
+            self.data.encryption.as_deref()
+        }
+    }
+
+    /// Sets encryption of the blocks, recorded with the next posted block.
+    pub fn set_encryption(&mut self, encryption: Option<String>) {
+        self.data.encryption = encryption;
+    }
+
+    /// Records a posted block of the given `format`, with its `hash`, and writes the journal to
+    /// disk.
+    pub fn record(&mut self, format: &str, hash: &str) -> Result<(), Error> {
+        self.data.format = String::from(format);
+        self.data.blocks.push(String::from(hash));
+
+        self.save()
+    }
+
+    /// Returns hash of the posted manifest, [None] if the manifest was not posted.
+    pub fn manifest(&self) -> Option<&str> {
+        self.data.manifest.as_deref()
+    }
+
+    /// Records the posted manifest, with its `hash`, and writes the journal to disk.
+    pub fn record_manifest(&mut self, hash: &str) -> Result<(), Error> {
+        self.data.manifest = Some(String::from(hash));
+
+        self.save()
+    }
+
+    /// Forgets every posted block and manifest, to start the upload over.
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.data = Data::default();
+        self.remove()
+    }
+
+    /// Removes journal from disk, once the upload is finished.
+    pub fn remove(&self) -> Result<(), Error> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec(&self.data)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    const DIGEST: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("seresa-journal-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn digests_files() {
+        let dir = temp_dir("digests");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("empty.txt");
+        fs::write(&path, b"").unwrap();
+
+        assert_eq!(file_digest(&path).unwrap(), DIGEST);
+        assert!(file_digest(&dir.join("missing.txt")).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn opens_missing_journals_empty() {
+        let dir = temp_dir("missing");
+        let journal = Journal::open(&dir, "localhost:8330", "#forum", DIGEST).unwrap();
+
+        assert!(journal.blocks().is_empty());
+        assert_eq!(journal.format(), None);
+        assert_eq!(journal.encryption(), None);
+        assert_eq!(journal.manifest(), None);
+    }
+
+    #[test]
+    fn records_posts() {
+        let dir = temp_dir("records");
+        let mut journal = Journal::open(&dir, "localhost:8330", "#forum", DIGEST).unwrap();
+        journal.set_encryption(Some(String::from("header")));
+        assert_eq!(journal.encryption(), None);
+        journal.record("format", "1_A").unwrap();
+        journal.record("format", "2_B").unwrap();
+        journal.record_manifest("3_C").unwrap();
+
+        let journal = Journal::open(&dir, "localhost:8330", "#forum", DIGEST).unwrap();
+        assert_eq!(journal.blocks(), ["1_A", "2_B"]);
+        assert_eq!(journal.format(), Some("format"));
+        assert_eq!(journal.encryption(), Some("header"));
+        assert_eq!(journal.manifest(), Some("3_C"));
+
+        // Journals of other chains are kept apart.
+        let other = Journal::open(&dir, "localhost:8330", "#other", DIGEST).unwrap();
+        assert!(other.blocks().is_empty());
+        assert_eq!(other.manifest(), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn clears_and_removes_journals() {
+        let dir = temp_dir("clears");
+        let mut journal = Journal::open(&dir, "localhost:8330", "#forum", DIGEST).unwrap();
+        journal.record("format", "1_A").unwrap();
+        journal.record_manifest("2_B").unwrap();
+        journal.clear().unwrap();
+        assert!(journal.blocks().is_empty());
+        assert_eq!(journal.manifest(), None);
+
+        let mut journal = Journal::open(&dir, "localhost:8330", "#forum", DIGEST).unwrap();
+        assert!(journal.blocks().is_empty());
+        journal.record("format", "1_A").unwrap();
+        journal.remove().unwrap();
+        journal.remove().unwrap();
+
+        let journal = Journal::open(&dir, "localhost:8330", "#forum", DIGEST).unwrap();
+        assert!(journal.blocks().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,23 +1,373 @@
+pub mod bibtex;
+pub mod citation;
+pub mod fulltext;
+pub mod index;
+pub mod journal;
+pub mod query;
+pub mod uri;
+
 use freechains::{ChainClient, Connect};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
+use std::cmp::Reverse;
+use std::collections::HashMap;
 use std::error;
 use std::fmt;
 use std::io;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Current version of the [Share] post schema.
+pub const SHARE_SCHEMA_VERSION: u32 = 2;
+
+/// Share forum post, containing a reference to a research paper.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Share {
+    /// Version of the post schema. Posts created before versioning have no version field and
+    /// are read as version `0`.
+    #[serde(default)]
+    pub schema_version: u32,
 
-#[derive(Debug, Deserialize, Serialize)]
-struct Share {
     /// Title of the article.
-    title: String,
+    pub title: String,
 
     /// Authors of the article.
-    authors: Vec<String>,
+    pub authors: Vec<String>,
 
     /// Tags of the article.
-    tags: Vec<String>,
+    pub tags: Vec<String>,
 
     /// Article URI.
-    uri: String,
+    pub uri: String,
+
+    /// Digital Object Identifier of the article, such as `10.1000/xyz123`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doi: Option<String>,
+
+    /// arXiv identifier of the article, such as `2101.00001`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arxiv_id: Option<String>,
+
+    /// Publication year of the article.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub year: Option<u16>,
+
+    /// Venue or journal where the article was published.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub venue: Option<String>,
+
+    /// Abstract of the article.
+    #[serde(rename = "abstract", default, skip_serializing_if = "Option::is_none")]
+    pub abstract_text: Option<String>,
+
+    /// License under which the article is distributed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+
+    /// Language of the article, as a language tag such as `en` or `pt-BR`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+impl Share {
+    /// Creates a new [Share] on the current schema version.
+    pub fn new(title: &str, authors: &[&str], tags: &[&str], uri: &str) -> Share {
+        Share {
+            schema_version: SHARE_SCHEMA_VERSION,
+            title: String::from(title),
+            authors: authors.iter().map(|&a| String::from(a)).collect(),
+            tags: tags.iter().map(|&t| String::from(t)).collect(),
+            uri: String::from(uri),
+            doi: None,
+            arxiv_id: None,
+            year: None,
+            venue: None,
+            abstract_text: None,
+            license: None,
+            language: None,
+        }
+    }
+
+    /// Validates the post fields, returning an [InputError](Error::InputError) describing the
+    /// first invalid field found.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.title.trim().is_empty() {
+            return Err(Error::InputError(String::from("title must not be empty")));
+        }
+
+        if self.uri.starts_with("fchs:") {
+            self.uri.parse::<uri::FchsUri>()?;
+        }
+
+        if let Some(doi) = &self.doi {
+            if !is_valid_doi(doi) {
+                return Err(Error::InputError(format!("invalid DOI \"{}\"", doi)));
+            }
+        }
+
+        if let Some(arxiv_id) = &self.arxiv_id {
+            if !is_valid_arxiv_id(arxiv_id) {
+                return Err(Error::InputError(format!(
+                    "invalid arXiv ID \"{}\"",
+                    arxiv_id
+                )));
+            }
+        }
+
+        if let Some(year) = self.year {
+            let max_year = current_year() + 1;
+            if year < 1000 || year > max_year {
+                return Err(Error::InputError(format!(
+                    "implausible publication year {}, must be between 1000 and {}",
+                    year, max_year
+                )));
+            }
+        }
+
+        if let Some(language) = &self.language {
+            if !is_valid_language(language) {
+                return Err(Error::InputError(format!(
+                    "invalid language tag \"{}\"",
+                    language
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses a post payload, migrating older schema versions to the current one.
+    ///
+    /// Posts on newer schema versions are accepted as long as they keep the fields known by this
+    /// version, unknown fields are ignored.
+    pub fn from_payload(payload: &[u8]) -> Result<Share, Error> {
+        let mut value: serde_json::Value = serde_json::from_slice(payload)?;
+        migrate_share(&mut value);
+
+        let share = serde_json::from_value(value)?;
+        Ok(share)
+    }
+}
+
+/// Migrates a raw [Share] post, one schema version at a time, up to [SHARE_SCHEMA_VERSION].
+fn migrate_share(value: &mut serde_json::Value) {
+    let obj = match value.as_object_mut() {
+        Some(obj) => obj,
+        None => return,
+    };
+
+    let mut version = obj
+        .get("schema_version")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+    while version < SHARE_SCHEMA_VERSION as u64 {
+        match version {
+            // Version 0 has the same fields as version 1, without the version field.
+            0 => {}
+            // Version 2 only adds optional bibliographic fields.
+            1 => {}
+            _ => unreachable!("missing migration for share schema version {}", version),
+        }
+        version += 1;
+    }
+    obj.insert(String::from("schema_version"), version.into());
+}
+
+/// Checks DOI syntax: `10.<registrant>/<suffix>`, where registrant is made of dot separated
+/// digits and suffix is not empty.
+fn is_valid_doi(doi: &str) -> bool {
+    let (prefix, suffix) = match doi.split_once('/') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let registrant = match prefix.strip_prefix("10.") {
+        Some(registrant) => registrant,
+        None => return false,
+    };
+
+    !registrant.is_empty()
+        && registrant
+            .split('.')
+            .all(|r| !r.is_empty() && r.chars().all(|c| c.is_ascii_digit()))
+        && !suffix.is_empty()
+        && !suffix.chars().any(char::is_whitespace)
+}
+
+/// Checks arXiv identifier syntax, either on the `YYMM.NNNNN` scheme, or on the old
+/// `archive/YYMMNNN` scheme. Both may have a version suffix, such as `v2`.
+fn is_valid_arxiv_id(id: &str) -> bool {
+    let id = match id.rsplit_once('v') {
+        Some((id, version))
+            if !version.is_empty() && version.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            id
+        }
+        _ => id,
+    };
+
+    match id.split_once('/') {
+        Some((archive, number)) => {
+            !archive.is_empty()
+                && archive
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c == '-' || c == '.')
+                && number.len() == 7
+                && number.chars().all(|c| c.is_ascii_digit())
+        }
+        None => match id.split_once('.') {
+            Some((date, number)) => {
+                date.len() == 4
+                    && (number.len() == 4 || number.len() == 5)
+                    && date
+                        .chars()
+                        .chain(number.chars())
+                        .all(|c| c.is_ascii_digit())
+            }
+            None => false,
+        },
+    }
+}
+
+/// Checks language tag syntax, as a primary language subtag of 2 or 3 letters followed by
+/// optional alphanumeric subtags, such as `en`, `por` or `pt-BR`.
+fn is_valid_language(language: &str) -> bool {
+    let mut subtags = language.split('-');
+    let primary = subtags.next().unwrap_or_default();
+
+    (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags
+            .all(|t| (1..=8).contains(&t.len()) && t.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Returns current year, approximated from system time.
+fn current_year() -> u16 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    // Average gregorian year length, in seconds.
+    (1970 + secs / 31_556_952) as u16
+}
+
+/// Share post with its chain metadata, returned by [get_share].
+#[derive(Debug, Serialize)]
+pub struct ShareInfo {
+    /// Post hash.
+    pub hash: String,
+
+    /// Post reputation on the chain.
+    pub reps: isize,
+
+    /// Post position on the chain consensus, [None] if post is not on consensus.
+    pub consensus_position: Option<usize>,
+
+    /// Post creation time, in milliseconds since [UNIX_EPOCH].
+    pub time: usize,
+
+    /// Public key of the post signer, [None] if post is anonymous.
+    pub signer: Option<String>,
+
+    /// Post content.
+    #[serde(flatten)]
+    pub share: Share,
+}
+
+/// Share found on a chain by [search_all].
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    /// Post hash.
+    pub hash: String,
+
+    /// Post reputation on the chain.
+    pub reps: isize,
+
+    /// Post content.
+    #[serde(flatten)]
+    pub share: Share,
+
+    /// Snippet of the referenced resource text matched by a full text search.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+/// Ordering of [search_all] results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sort {
+    /// Most matched query terms, or search strings, first.
+    Relevance,
+
+    /// Highest reputation first.
+    Reps,
+
+    /// Latest posts on chain consensus first.
+    Recent,
+}
+
+impl Sort {
+    /// Names accepted by [FromStr].
+    pub const NAMES: &'static [&'static str] = &["relevance", "reps", "recent"];
+}
+
+impl FromStr for Sort {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "relevance" => Ok(Sort::Relevance),
+            "reps" => Ok(Sort::Reps),
+            "recent" => Ok(Sort::Recent),
+            _ => Err(Error::InputError(format!("unknown sort order \"{}\"", s))),
+        }
+    }
+}
+
+/// Output mode of commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    /// Human readable text.
+    Text,
+
+    /// JSON objects, one per line, for scripts.
+    Json,
+}
+
+impl Output {
+    /// Names accepted by [FromStr].
+    pub const NAMES: &'static [&'static str] = &["text", "json"];
+
+    /// Writes an output line: `text` on [Text](Output::Text) output, or `value` as a single line
+    /// JSON object on [Json](Output::Json) output.
+    pub fn write_line(
+        self,
+        mut w: impl io::Write,
+        text: impl fmt::Display,
+        value: &serde_json::Value,
+    ) -> Result<(), Error> {
+        match self {
+            Output::Text => writeln!(w, "{}", text)?,
+            Output::Json => {
+                serde_json::to_writer(&mut w, value)?;
+                writeln!(w)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Output {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Output::Text),
+            "json" => Ok(Output::Json),
+            _ => Err(Error::InputError(format!("unknown output mode \"{}\"", s))),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -28,6 +378,37 @@ pub enum Error {
     InputError(String),
     InvalidContentError(String, String),
     LowReputationError(String, String),
+    IntegrityError(String, String),
+    DecryptionError(String, String),
+    ConsensusError(String, String),
+}
+
+impl Error {
+    /// Stable code identifying the error kind, such as `input` or `low_reputation`, to be used on
+    /// structured error outputs.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::SerdeJsonError(_) => "json",
+            Error::FreechainsError(_) => "freechains",
+            Error::IoError(_) => "io",
+            Error::InputError(_) => "input",
+            Error::InvalidContentError(_, _) => "invalid_content",
+            Error::LowReputationError(_, _) => "low_reputation",
+            Error::IntegrityError(_, _) => "integrity",
+            Error::DecryptionError(_, _) => "decryption",
+            Error::ConsensusError(_, _) => "consensus",
+        }
+    }
+
+    /// Returns the error as a JSON object, with its [code](Error::code) and message.
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "error": {
+                "code": self.code(),
+                "message": self.to_string(),
+            }
+        })
+    }
 }
 
 impl error::Error for Error {}
@@ -47,6 +428,21 @@ impl fmt::Display for Error {
                 "Low reputation content on chain \"{}\", post \"{}\"",
                 c, p
             ),
+            Error::IntegrityError(c, p) => write!(
+                f,
+                "Integrity check failed on chain \"{}\", post \"{}\"",
+                c, p
+            ),
+            Error::DecryptionError(c, p) => write!(
+                f,
+                "Could not decrypt content on chain \"{}\", post \"{}\", wrong key or passphrase",
+                c, p
+            ),
+            Error::ConsensusError(c, p) => write!(
+                f,
+                "Content not on consensus of chain \"{}\", post \"{}\"",
+                c, p
+            ),
         }
     }
 }
@@ -69,104 +465,573 @@ impl From<io::Error> for Error {
     }
 }
 
-pub fn share_article<T: Connect>(
+pub fn share_article<T>(
+    w: impl io::Write,
+    chain_client: &ChainClient<T>,
+    signature: Option<&str>,
+    share: &Share,
+    output: Output,
+) -> Result<(), Error>
+where
+    T: Connect,
+{
+    let hash = post_share(chain_client, signature, share)?;
+    output.write_line(w, &hash, &json!({ "hash": hash }))?;
+
+    Ok(())
+}
+
+/// Shares every entry of a BibTeX `input`, printing a table of citation keys and post hashes. On
+/// [Json](Output::Json) output, a line is printed for each entry, with its key and either its post
+/// hash or the reason it was skipped.
+///
+/// Entries with the same DOI or title of a share already on the chain consensus, or of a previous
+/// entry, are skipped. Entries which cannot be mapped to a valid [Share] are reported and skipped.
+/// The chain `index` is updated to find the shares on the chain consensus.
+pub fn import_bibtex<T>(
     mut w: impl io::Write,
     chain_client: &ChainClient<T>,
+    index: &mut index::Index,
     signature: Option<&str>,
-    title: &str,
-    authors: &[&str],
-    tags: &[&str],
-    uri: &str,
+    input: &str,
+    output: Output,
 ) -> Result<(), Error>
 where
     T: Connect,
 {
-    let title = String::from(title);
-    let authors: Vec<_> = authors.iter().map(|&a| String::from(a)).collect();
-    let tags: Vec<_> = tags.iter().map(|&t| String::from(t)).collect();
-    let uri = String::from(uri);
-    let share = Share {
-        title,
-        authors,
-        tags,
-        uri,
-    };
+    let entries = bibtex::parse(input)?;
+
+    index.update(chain_client)?;
+    index.save()?;
+
+    let mut dois = HashMap::new();
+    let mut titles = HashMap::new();
+    for (hash, share, _) in index.shares() {
+        if let Some(doi) = &share.doi {
+            dois.insert(doi.to_lowercase(), String::from(hash));
+        }
+        titles.insert(normalize_title(&share.title), String::from(hash));
+    }
 
-    let payload = serde_json::to_vec(&share)?;
+    let width = entries.iter().map(|e| e.key.len()).max().unwrap_or(0);
+    for entry in entries {
+        let share = match entry.to_share().and_then(|s| s.validate().map(|_| s)) {
+            Ok(share) => share,
+            Err(e) => {
+                output.write_line(
+                    &mut w,
+                    format_args!("{:width$}  skipped: {}", entry.key, e, width = width),
+                    &json!({ "key": entry.key, "skipped": e.to_string(), "code": e.code() }),
+                )?;
+                continue;
+            }
+        };
+
+        let doi = share.doi.as_ref().map(|d| d.to_lowercase());
+        let title = normalize_title(&share.title);
+        let existing = doi
+            .as_ref()
+            .and_then(|d| dois.get(d))
+            .or_else(|| titles.get(&title));
+        if let Some(existing) = existing {
+            output.write_line(
+                &mut w,
+                format_args!(
+                    "{:width$}  skipped: already shared on {}",
+                    entry.key,
+                    existing,
+                    width = width
+                ),
+                &json!({ "key": entry.key, "skipped": "already shared", "existing": existing }),
+            )?;
+            continue;
+        }
+
+        let hash = post_share(chain_client, signature, &share)?;
+        output.write_line(
+            &mut w,
+            format_args!("{:width$}  {}", entry.key, hash, width = width),
+            &json!({ "key": entry.key, "hash": hash }),
+        )?;
+
+        if let Some(doi) = doi {
+            dois.insert(doi, hash.clone());
+        }
+        titles.insert(title, hash);
+    }
+
+    Ok(())
+}
+
+/// Validates and posts `share`, returning the post hash.
+fn post_share<T>(
+    chain_client: &ChainClient<T>,
+    signature: Option<&str>,
+    share: &Share,
+) -> Result<String, Error>
+where
+    T: Connect,
+{
+    share.validate()?;
+
+    let payload = serde_json::to_vec(share)?;
 
     let hash = chain_client.post(signature, false, &payload)?;
-    writeln!(w, "{}", hash)?;
+    Ok(hash)
+}
+
+/// Normalizes a title for comparison, ignoring case, punctuation and spacing.
+fn normalize_title(title: &str) -> String {
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Updates the local `index` of a chain, printing the number of fetched posts and indexed
+/// shares. If `rebuild` is set, the index is cleared before the update.
+///
+/// If `fetch_resource` is defined, the text of resources referenced by shares is also indexed,
+/// fetching the resource contents from its URI.
+pub fn index_chain<T>(
+    mut w: impl io::Write,
+    chain_client: &ChainClient<T>,
+    index: &mut index::Index,
+    rebuild: bool,
+    fetch_resource: Option<&mut index::FetchResource>,
+    output: Output,
+) -> Result<(), Error>
+where
+    T: Connect,
+{
+    if rebuild {
+        index.clear()?;
+    }
+
+    let fetched = index.update(chain_client)?;
+    index.save()?;
+
+    output.write_line(
+        &mut w,
+        format_args!(
+            "{} new posts fetched, {} shares indexed",
+            fetched,
+            index.len()
+        ),
+        &json!({ "fetched": fetched, "shares": index.len() }),
+    )?;
+
+    if let Some(fetch_resource) = fetch_resource {
+        let indexed = index.index_texts(fetch_resource)?;
+        index.save()?;
+
+        output.write_line(
+            &mut w,
+            format_args!("{} new resource texts indexed", indexed),
+            &json!({ "texts": indexed }),
+        )?;
+    }
 
     Ok(())
 }
 
+/// Searches chain consensus for shares matching `query`, writing them on the given `format`.
+///
+/// The chain `index` is updated before the search, so only posts not indexed yet are fetched,
+/// and the reputation of found shares is refreshed. Shares with reputation lower than
+/// `min_reps`, if defined, are left out.
 pub fn search_all<T>(
-    mut w: impl io::Write,
+    w: impl io::Write,
     chain_client: &ChainClient<T>,
+    index: &mut index::Index,
+    query: &query::Query,
+    min_reps: Option<isize>,
+    sort: Sort,
+    format: citation::Format,
+) -> Result<(), Error>
+where
+    T: Connect,
+{
+    index.update(chain_client)?;
+
+    let hashes: Vec<_> = index
+        .shares()
+        .filter(|(_, share, _)| query.matches(share))
+        .map(|(hash, _, _)| String::from(hash))
+        .collect();
+    for hash in hashes {
+        let reps = chain_client.reputation(&hash)?;
+        index.set_reps(&hash, reps);
+    }
+    index.save()?;
+
+    search_index(w, index, query, min_reps, sort, format)
+}
+
+/// Searches the local `index` for shares matching `query`, without contacting the Freechains
+/// node. Reputations are the ones known on the last index update.
+///
+/// See [search_all].
+pub fn search_index(
+    w: impl io::Write,
+    index: &index::Index,
+    query: &query::Query,
+    min_reps: Option<isize>,
+    sort: Sort,
+    format: citation::Format,
+) -> Result<(), Error> {
+    let mut results = Vec::new();
+    for (position, (hash, share, reps)) in index.shares().enumerate() {
+        if !query.matches(share) || min_reps.map(|m| reps < m).unwrap_or(false) {
+            continue;
+        }
+
+        let relevance = query.relevance(share);
+        let result = SearchResult {
+            hash: String::from(hash),
+            reps,
+            share: share.clone(),
+            snippet: None,
+        };
+        results.push((position, relevance, result));
+    }
+
+    citation::write(w, format, &rank(results, sort))
+}
+
+/// Searches the text of resources on the local `index` for any of `strings`, ignoring case, and
+/// writes the matching shares on the given `format`, with a snippet of the first match.
+///
+/// Resources texts must have been indexed by [index_chain]. See [search_index].
+pub fn search_text(
+    w: impl io::Write,
+    index: &index::Index,
     strings: &[&str],
+    min_reps: Option<isize>,
+    sort: Sort,
+    format: citation::Format,
+) -> Result<(), Error> {
+    let mut results = Vec::new();
+    for (position, (hash, share, reps)) in index.shares().enumerate() {
+        if min_reps.map(|m| reps < m).unwrap_or(false) {
+            continue;
+        }
+        let text = match index.text(hash)? {
+            Some(text) => text,
+            None => continue,
+        };
+
+        let snippets: Vec<_> = strings
+            .iter()
+            .filter_map(|s| fulltext::find_snippet(&text, s))
+            .collect();
+        if let Some(snippet) = snippets.first() {
+            let result = SearchResult {
+                hash: String::from(hash),
+                reps,
+                share: share.clone(),
+                snippet: Some(snippet.clone()),
+            };
+            results.push((position, snippets.len(), result));
+        }
+    }
+
+    citation::write(w, format, &rank(results, sort))
+}
+
+/// Sorts search results, given with their consensus position and relevance.
+fn rank(mut results: Vec<(usize, usize, SearchResult)>, sort: Sort) -> Vec<SearchResult> {
+    match sort {
+        Sort::Relevance => results.sort_by_key(|&(_, relevance, _)| Reverse(relevance)),
+        Sort::Reps => results.sort_by_key(|(_, _, r)| Reverse(r.reps)),
+        Sort::Recent => results.sort_by_key(|&(position, _, _)| Reverse(position)),
+    }
+
+    results.into_iter().map(|(_, _, r)| r).collect()
+}
+
+/// Gets share post `hash` with its chain metadata.
+pub fn get_share<T>(chain_client: &ChainClient<T>, hash: &str) -> Result<ShareInfo, Error>
+where
+    T: Connect,
+{
+    let payload = chain_client.payload(hash, None)?;
+    let share = Share::from_payload(&payload)?;
+
+    let content = chain_client.content(hash, None)?;
+    let reps = chain_client.reputation(hash)?;
+    let consensus_position = chain_client.consensus()?.iter().position(|h| h == hash);
+
+    Ok(ShareInfo {
+        hash: String::from(hash),
+        reps,
+        consensus_position,
+        time: content.time,
+        signer: content.signature.map(|s| s.pubkey),
+        share,
+    })
+}
+
+/// Prints every field of share post `hash`, with its chain metadata. On [Json](Output::Json)
+/// output, prints it as a single line JSON object instead.
+pub fn show_share<T>(
+    mut w: impl io::Write,
+    chain_client: &ChainClient<T>,
+    hash: &str,
+    output: Output,
 ) -> Result<(), Error>
 where
     T: Connect,
 {
-    let consensus = chain_client.consensus()?;
-
-    let strings: Vec<_> = strings.iter().map(|s| s.to_lowercase()).collect();
-    for hash in consensus {
-        let payload = chain_client.payload(&hash, None)?;
-
-        if let Ok(share) = serde_json::from_slice::<Share>(&payload) {
-            for s in &strings {
-                let s = s.as_str();
-                let found = share.title.to_lowercase().contains(&s)
-                    || share
-                        .authors
-                        .iter()
-                        .find(|&a| a.to_lowercase().contains(&s))
-                        .is_some()
-                    || share
-                        .tags
-                        .iter()
-                        .find(|&t| t.to_lowercase().contains(&s))
-                        .is_some();
-                if found {
-                    writeln!(w, "{}", hash)?;
-                    break;
-                }
-            }
+    let info = get_share(chain_client, hash)?;
+
+    if output == Output::Json {
+        serde_json::to_writer(&mut w, &info)?;
+        writeln!(w)?;
+        return Ok(());
+    }
+
+    let share = &info.share;
+    let mut fields = vec![
+        ("Hash", info.hash.clone()),
+        ("Title", share.title.clone()),
+        ("Authors", share.authors.join("; ")),
+        ("Tags", share.tags.join(", ")),
+        ("URI", share.uri.clone()),
+    ];
+    let optional_fields = [
+        ("DOI", share.doi.clone()),
+        ("arXiv ID", share.arxiv_id.clone()),
+        ("Year", share.year.map(|y| y.to_string())),
+        ("Venue", share.venue.clone()),
+        ("License", share.license.clone()),
+        ("Language", share.language.clone()),
+    ];
+    for (name, value) in optional_fields {
+        if let Some(value) = value {
+            fields.push((name, value));
         }
     }
+    fields.push(("Reputation", info.reps.to_string()));
+    fields.push((
+        "Consensus",
+        match info.consensus_position {
+            Some(p) => format!("position {}", p),
+            None => String::from("not on consensus"),
+        },
+    ));
+    fields.push(("Posted", format_time(info.time)));
+    fields.push((
+        "Signed by",
+        info.signer
+            .clone()
+            .unwrap_or_else(|| String::from("anonymous")),
+    ));
+
+    for (name, value) in fields {
+        writeln!(w, "{:<11} {}", format!("{}:", name), value)?;
+    }
+    if let Some(abstract_text) = &share.abstract_text {
+        writeln!(w)?;
+        writeln!(w, "{}", abstract_text)?;
+    }
 
     Ok(())
 }
 
+/// Formats `millis` since [UNIX_EPOCH] as an UTC date and time, such as `2021-09-30 18:05:02 UTC`.
+fn format_time(millis: usize) -> String {
+    let secs = millis / 1000;
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since epoch, as described in
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
 pub fn get_uri<T>(
-    mut w: impl io::Write,
+    w: impl io::Write,
     chain_client: &ChainClient<T>,
     hash: &str,
+    output: Output,
 ) -> Result<(), Error>
 where
     T: Connect,
 {
     let payload = chain_client.payload(hash, None)?;
 
-    let share: Share = serde_json::from_slice(&payload)?;
-    writeln!(w, "{}", share.uri)?;
+    let share = Share::from_payload(&payload)?;
+    output.write_line(w, &share.uri, &json!({ "uri": share.uri }))?;
 
     Ok(())
 }
 
 pub fn get_title<T>(
-    mut w: impl io::Write,
+    w: impl io::Write,
     chain_client: &ChainClient<T>,
     hash: &str,
+    output: Output,
 ) -> Result<(), Error>
 where
     T: Connect,
 {
     let payload = chain_client.payload(hash, None)?;
 
-    let share: Share = serde_json::from_slice(&payload)?;
-    writeln!(w, "{}", share.title)?;
+    let share = Share::from_payload(&payload)?;
+    output.write_line(w, &share.title, &json!({ "title": share.title }))?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn share() -> Share {
+        Share::new(
+            "Freenet",
+            &["Ian Clarke"],
+            &["p2p"],
+            "https://example.site/freenet.pdf",
+        )
+    }
+
+    #[test]
+    fn validates_shares() {
+        let mut share = share();
+        share.doi = Some(String::from("10.1007/3-540-44702-4_4"));
+        share.arxiv_id = Some(String::from("2101.00001v2"));
+        share.year = Some(2001);
+        share.language = Some(String::from("pt-BR"));
+        assert!(share.validate().is_ok());
+
+        let mut untitled = self::share();
+        untitled.title = String::from("  ");
+        assert!(matches!(untitled.validate(), Err(Error::InputError(_))));
+    }
+
+    #[test]
+    fn rejects_implausible_years() {
+        let mut share = share();
+
+        share.year = Some(current_year() + 1);
+        assert!(share.validate().is_ok());
+        share.year = Some(current_year() + 2);
+        assert!(matches!(share.validate(), Err(Error::InputError(_))));
+        share.year = Some(999);
+        assert!(matches!(share.validate(), Err(Error::InputError(_))));
+    }
+
+    #[test]
+    fn checks_doi_syntax() {
+        for doi in &["10.1000/xyz123", "10.1000.10/a/b", "10.1/(SICI)1099"] {
+            assert!(is_valid_doi(doi), "{:?} was refused", doi);
+        }
+        for doi in &[
+            "",
+            "10.1000",
+            "10.1000/",
+            "10./xyz",
+            "11.1000/xyz",
+            "10.10a0/xyz",
+            "10.1000..1/xyz",
+            "10.1000/x y",
+            "doi:10.1000/xyz",
+        ] {
+            assert!(!is_valid_doi(doi), "{:?} was accepted", doi);
+        }
+    }
+
+    #[test]
+    fn checks_arxiv_id_syntax() {
+        for id in &[
+            "2101.00001",
+            "0704.0001",
+            "2101.00001v12",
+            "hep-th/9901001",
+            "cond-mat/0011267v1",
+        ] {
+            assert!(is_valid_arxiv_id(id), "{:?} was refused", id);
+        }
+        for id in &[
+            "",
+            "2101.001",
+            "2101.000001",
+            "21010.0001",
+            "2101.00001v",
+            "2101-00001",
+            "HEP-TH/9901001",
+            "hep-th/990100",
+            "/9901001",
+        ] {
+            assert!(!is_valid_arxiv_id(id), "{:?} was accepted", id);
+        }
+    }
+
+    #[test]
+    fn checks_language_tag_syntax() {
+        for language in &["en", "por", "pt-BR", "zh-Hant-TW", "de-1996"] {
+            assert!(is_valid_language(language), "{:?} was refused", language);
+        }
+        for language in &["", "e", "engl", "pt-", "pt_BR", "p1", "en-abcdefghi"] {
+            assert!(!is_valid_language(language), "{:?} was accepted", language);
+        }
+    }
+
+    #[test]
+    fn migrates_unversioned_shares() {
+        let payload = br#"{"title":"Freenet","authors":["Ian Clarke"],"tags":[],"uri":"x"}"#;
+        let share = Share::from_payload(payload).unwrap();
+
+        assert_eq!(share.schema_version, SHARE_SCHEMA_VERSION);
+        assert_eq!(share.title, "Freenet");
+        assert_eq!(share.doi, None);
+    }
+
+    #[test]
+    fn migrates_version_1_shares() {
+        let mut value = serde_json::json!({
+            "schema_version": 1,
+            "title": "Freenet",
+            "authors": ["Ian Clarke"],
+            "tags": ["p2p"],
+            "uri": "x",
+        });
+        migrate_share(&mut value);
+        assert_eq!(value["schema_version"], SHARE_SCHEMA_VERSION);
+
+        let share = Share::from_payload(value.to_string().as_bytes()).unwrap();
+        assert_eq!(share.tags, vec!["p2p"]);
+        assert_eq!(share.year, None);
+    }
+
+    #[test]
+    fn keeps_newer_shares() {
+        let payload = br#"{"schema_version":99,"title":"T","authors":[],"tags":[],"uri":"x",
+            "year":2001,"unknown":true}"#;
+        let share = Share::from_payload(payload).unwrap();
+
+        assert_eq!(share.schema_version, 99);
+        assert_eq!(share.year, Some(2001));
+    }
+}
@@ -1,17 +1,55 @@
+mod crypt;
 mod resource;
 
-use clap::{App, Arg, SubCommand};
+use clap::{App, Arg, ArgGroup, ArgMatches, SubCommand};
 use freechains::{ChainId, Client};
+use serde_json::json;
+use seresa::fulltext;
+use seresa::uri::FchsUri;
+use seresa::Output;
 
-use std::error::Error;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io;
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() {
     let matches = App::new("seresa")
         .version("0.1.0")
         .author("Breno Brandão <lrabbt@gmail.com>")
         .about("Shares and searches research papers on a Freechains network.")
+        .arg(
+            Arg::with_name("host")
+                .short("h")
+                .long("host")
+                .help("Freechains server hostname.")
+                .takes_value(true)
+                .global(true)
+                .value_name("HOST"),
+        )
+        .arg(
+            Arg::with_name("port")
+                .short("p")
+                .long("port")
+                .help("Freechains server port.")
+                .takes_value(true)
+                .global(true)
+                .value_name("PORT"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .help(
+                    "Output mode. On 'json', results and errors are printed as JSON objects, \
+            one per line.",
+                )
+                .takes_value(true)
+                .possible_values(Output::NAMES)
+                .default_value("text")
+                .global(true)
+                .value_name("MODE"),
+        )
         .subcommand(
             SubCommand::with_name("share")
                 .about("Manages and searches share forum posts.")
@@ -24,24 +62,6 @@ fn main() -> Result<(), Box<dyn Error>> {
                         .required(true)
                         .value_name("CHAIN"),
                 )
-                .arg(
-                    Arg::with_name("host")
-                        .short("h")
-                        .long("host")
-                        .help("Freechains server hostname.")
-                        .takes_value(true)
-                        .global(true)
-                        .value_name("HOST"),
-                )
-                .arg(
-                    Arg::with_name("port")
-                        .short("p")
-                        .long("port")
-                        .help("Freechains server port.")
-                        .takes_value(true)
-                        .global(true)
-                        .value_name("PORT"),
-                )
                 .subcommand(
                     SubCommand::with_name("post")
                         .about("Shares research URI.")
@@ -59,7 +79,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                                 .long("title")
                                 .help("Article's title")
                                 .takes_value(true)
-                                .required(true)
+                                .required_unless("bibtex")
                                 .value_name("TITLE"),
                         )
                         .arg(
@@ -87,8 +107,68 @@ fn main() -> Result<(), Box<dyn Error>> {
                                 .long("uri")
                                 .help("Article's URI")
                                 .takes_value(true)
-                                .required(true)
+                                .required_unless("bibtex")
                                 .value_name("URI"),
+                        )
+                        .arg(
+                            Arg::with_name("doi")
+                                .long("doi")
+                                .help("Article's DOI")
+                                .takes_value(true)
+                                .value_name("DOI"),
+                        )
+                        .arg(
+                            Arg::with_name("arxiv")
+                                .long("arxiv")
+                                .help("Article's arXiv ID")
+                                .takes_value(true)
+                                .value_name("ARXIV_ID"),
+                        )
+                        .arg(
+                            Arg::with_name("year")
+                                .long("year")
+                                .help("Article's publication year")
+                                .takes_value(true)
+                                .value_name("YEAR"),
+                        )
+                        .arg(
+                            Arg::with_name("venue")
+                                .long("venue")
+                                .help("Article's venue or journal")
+                                .takes_value(true)
+                                .value_name("VENUE"),
+                        )
+                        .arg(
+                            Arg::with_name("abstract")
+                                .long("abstract")
+                                .help("Article's abstract")
+                                .takes_value(true)
+                                .value_name("ABSTRACT"),
+                        )
+                        .arg(
+                            Arg::with_name("license")
+                                .long("license")
+                                .help("Article's license")
+                                .takes_value(true)
+                                .value_name("LICENSE"),
+                        )
+                        .arg(
+                            Arg::with_name("language")
+                                .long("language")
+                                .help("Article's language tag, such as 'en' or 'pt-BR'")
+                                .takes_value(true)
+                                .value_name("LANGUAGE"),
+                        )
+                        .arg(
+                            Arg::with_name("bibtex")
+                                .long("bibtex")
+                                .help(
+                                    "BibTeX file with articles to be shared. \
+                            Entries already shared on the chain are skipped.",
+                                )
+                                .takes_value(true)
+                                .conflicts_with_all(&["title", "uri"])
+                                .value_name("FILE"),
                         ),
                 )
                 .subcommand(
@@ -103,12 +183,78 @@ fn main() -> Result<(), Box<dyn Error>> {
                             It is not case-sensitive.",
                                 )
                                 .takes_value(true)
-                                .required(true)
+                                .required_unless("query")
                                 .multiple(true)
                                 .number_of_values(1)
                                 .value_name("STRING"),
+                        )
+                        .arg(
+                            Arg::with_name("query")
+                                .short("q")
+                                .long("query")
+                                .help(
+                                    "Structured query, such as \
+                            'author:clarke AND (tag:=p2p OR NOT year:<2000)'.",
+                                )
+                                .takes_value(true)
+                                .conflicts_with("strings")
+                                .value_name("QUERY"),
+                        )
+                        .arg(
+                            Arg::with_name("format")
+                                .short("f")
+                                .long("format")
+                                .help("Output format of the found articles.")
+                                .takes_value(true)
+                                .possible_values(seresa::citation::Format::NAMES)
+                                .default_value("hashes")
+                                .value_name("FORMAT"),
+                        )
+                        .arg(
+                            Arg::with_name("min_reps")
+                                .long("min-reps")
+                                .help("Minimum reputation of the found articles.")
+                                .takes_value(true)
+                                .allow_hyphen_values(true)
+                                .value_name("REPS"),
+                        )
+                        .arg(
+                            Arg::with_name("sort")
+                                .long("sort")
+                                .help("Ordering of the found articles.")
+                                .takes_value(true)
+                                .possible_values(seresa::Sort::NAMES)
+                                .default_value("relevance")
+                                .value_name("ORDER"),
+                        )
+                        .arg(Arg::with_name("offline").long("offline").help(
+                            "Searches only the local index, without contacting the \
+                            Freechains server.",
+                        ))
+                        .arg(
+                            Arg::with_name("fulltext")
+                                .long("fulltext")
+                                .help(
+                                    "Searches strings on the text of resources referenced by \
+                            articles, indexed by 'index --fulltext'. Searches only the local \
+                            index.",
+                                )
+                                .conflicts_with("query"),
                         ),
                 )
+                .subcommand(
+                    SubCommand::with_name("index")
+                        .about("Updates local index of chain posts, used by searches.")
+                        .arg(
+                            Arg::with_name("rebuild")
+                                .long("rebuild")
+                                .help("Discards local index, fetching every post again."),
+                        )
+                        .arg(Arg::with_name("fulltext").long("fulltext").help(
+                            "Also indexes the text of plain text and PDF resources \
+                            referenced by articles 'fchs' URIs.",
+                        )),
+                )
                 .subcommand(
                     SubCommand::with_name("get-uri")
                         .about(
@@ -125,6 +271,48 @@ fn main() -> Result<(), Box<dyn Error>> {
                                 .value_name("HASH"),
                         ),
                 )
+                .subcommand(
+                    SubCommand::with_name("fetch")
+                        .about(
+                            "Fetches article from the URI of a post containing research paper \
+                    information. Supports 'fchs' and 'file' URIs.",
+                        )
+                        .arg(
+                            Arg::with_name("hash")
+                                .short("H")
+                                .long("hash")
+                                .help("Post's hash.")
+                                .takes_value(true)
+                                .required(true)
+                                .value_name("HASH"),
+                        )
+                        .arg(
+                            Arg::with_name("out")
+                                .short("o")
+                                .long("out")
+                                .help("Output file. If '-' or not present, prints on Stdout.")
+                                .takes_value(true)
+                                .value_name("FILE"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("Shows every field of a post containing research paper information.")
+                        .arg(
+                            Arg::with_name("hash")
+                                .short("H")
+                                .long("hash")
+                                .help("Post's hash.")
+                                .takes_value(true)
+                                .required(true)
+                                .value_name("HASH"),
+                        )
+                        .arg(
+                            Arg::with_name("json")
+                                .long("json")
+                                .help("Prints post as a JSON object, same as '--output json'."),
+                        ),
+                )
                 .subcommand(
                     SubCommand::with_name("get-title")
                         .about(
@@ -152,9 +340,14 @@ fn main() -> Result<(), Box<dyn Error>> {
                             Arg::with_name("chain")
                                 .short("c")
                                 .long("chain")
-                                .help("Chain name to be used as base for operation.")
+                                .help(
+                                    "Chain name to be used as base for operation. Repeated to \
+                            upload copies to other chains, listed as alternates of the first.",
+                                )
                                 .takes_value(true)
                                 .required(true)
+                                .multiple(true)
+                                .number_of_values(1)
                                 .value_name("CHAIN"),
                         )
                         .arg(
@@ -182,16 +375,81 @@ fn main() -> Result<(), Box<dyn Error>> {
                                 .takes_value(true)
                                 .required(true)
                                 .value_name("FILE"),
+                        )
+                        .arg(
+                            Arg::with_name("encoding")
+                                .long("encoding")
+                                .help(
+                                    "Encoding of the blocks contents. 'base64' blocks can be \
+                            downloaded by older versions.",
+                                )
+                                .takes_value(true)
+                                .possible_values(resource::Encoding::NAMES)
+                                .default_value("z85")
+                                .value_name("ENCODING"),
+                        )
+                        .arg(Arg::with_name("compress").long("compress").help(
+                            "Compresses the resource with DEFLATE before posting it, \
+                            reporting the space saved.",
+                        ))
+                        .arg(Arg::with_name("resume").long("resume").help(
+                            "Continues an interrupted upload of the same file to the same \
+                            chain, without posting again the blocks already posted.",
+                        ))
+                        .arg(
+                            Arg::with_name("encrypt")
+                                .long("encrypt")
+                                .help(
+                                    "Encrypts the resource with a passphrase or to a \
+                            recipient public key, so only who has the key can read it.",
+                                )
+                                .requires("encryption_key"),
+                        )
+                        .arg(
+                            Arg::with_name("passphrase")
+                                .long("passphrase")
+                                .help("Passphrase the resource is encrypted with.")
+                                .takes_value(true)
+                                .requires("encrypt")
+                                .value_name("PASSPHRASE"),
+                        )
+                        .arg(
+                            Arg::with_name("recipient")
+                                .long("recipient")
+                                .help(
+                                    "Public key of the user the resource is encrypted to, \
+                            who downloads it with their private key.",
+                                )
+                                .takes_value(true)
+                                .requires("encrypt")
+                                .value_name("PUBKEY"),
+                        )
+                        .arg(
+                            Arg::with_name("erasure")
+                                .long("erasure")
+                                .help(
+                                    "Posts parity blocks, so any K of each N blocks recover \
+                            the resource, such as '4:6'.",
+                                )
+                                .takes_value(true)
+                                .value_name("K:N"),
+                        )
+                        .group(
+                            ArgGroup::with_name("encryption_key")
+                                .args(&["passphrase", "recipient"]),
                         ),
                 )
                 .subcommand(
                     SubCommand::with_name("download")
                         .about("Downloads resource from Freechains chain.")
                         .arg(
-                            Arg::with_name("output")
+                            Arg::with_name("out")
                                 .short("o")
-                                .long("output")
-                                .help("Output file. If '-' or not present, prints on Stdout.")
+                                .long("out")
+                                .help(
+                                    "Output file. If '-' or not present, prints on Stdout. \
+                            Files are only written once the download is verified.",
+                                )
                                 .takes_value(true)
                                 .value_name("FILE"),
                         )
@@ -203,22 +461,140 @@ fn main() -> Result<(), Box<dyn Error>> {
                                 .takes_value(true)
                                 .required(true)
                                 .value_name("URI"),
-                        ),
+                        )
+                        .arg(
+                            Arg::with_name("jobs")
+                                .short("j")
+                                .long("jobs")
+                                .help(
+                                    "Number of blocks fetched in parallel, when the resource \
+                            has a manifest. Defaults to 4.",
+                                )
+                                .takes_value(true)
+                                .value_name("N"),
+                        )
+                        .arg(Arg::with_name("resume").long("resume").help(
+                            "Continues an interrupted download to the same output file, \
+                            fetching only the missing blocks.",
+                        ))
+                        .arg(
+                            Arg::with_name("passphrase")
+                                .long("passphrase")
+                                .help("Passphrase the resource was encrypted with.")
+                                .takes_value(true)
+                                .value_name("PASSPHRASE"),
+                        )
+                        .arg(
+                            Arg::with_name("key")
+                                .long("key")
+                                .help("User's private key, if the resource was encrypted to them.")
+                                .takes_value(true)
+                                .conflicts_with("passphrase")
+                                .value_name("PVTKEY"),
+                        )
+                        .arg(
+                            Arg::with_name("min_reps")
+                                .long("min-reps")
+                                .help("Minimum reputation of each block. Defaults to -3.")
+                                .takes_value(true)
+                                .allow_hyphen_values(true)
+                                .value_name("REPS"),
+                        )
+                        .arg(
+                            Arg::with_name("force")
+                                .long("force")
+                                .help("Downloads blocks regardless of their reputation.")
+                                .conflicts_with("min_reps"),
+                        )
+                        .arg(Arg::with_name("consensus").long("consensus").help(
+                            "Checks each block is on its chain consensus, instead of only \
+                            fetching its payload.",
+                        )),
+                )
+                .subcommand(
+                    SubCommand::with_name("mirror")
+                        .about("Mirrors resource to another Freechains chain.")
+                        .arg(
+                            Arg::with_name("from")
+                                .long("from")
+                                .help("URI of the mirrored resource.")
+                                .takes_value(true)
+                                .required(true)
+                                .value_name("URI"),
+                        )
+                        .arg(
+                            Arg::with_name("to")
+                                .long("to")
+                                .help("Chain name the resource is mirrored to.")
+                                .takes_value(true)
+                                .required(true)
+                                .value_name("CHAIN"),
+                        )
+                        .arg(
+                            Arg::with_name("signature")
+                                .short("s")
+                                .long("sign")
+                                .help("User's private key.")
+                                .takes_value(true)
+                                .value_name("SIGNATURE"),
+                        )
+                        .arg(
+                            Arg::with_name("jobs")
+                                .short("j")
+                                .long("jobs")
+                                .help(
+                                    "Number of blocks fetched in parallel, when the resource \
+                            has a manifest. Defaults to 4.",
+                                )
+                                .takes_value(true)
+                                .value_name("N"),
+                        )
+                        .arg(
+                            Arg::with_name("min_reps")
+                                .long("min-reps")
+                                .help("Minimum reputation of each block. Defaults to -3.")
+                                .takes_value(true)
+                                .allow_hyphen_values(true)
+                                .value_name("REPS"),
+                        )
+                        .arg(
+                            Arg::with_name("force")
+                                .long("force")
+                                .help("Mirrors blocks regardless of their reputation.")
+                                .conflicts_with("min_reps"),
+                        )
+                        .arg(Arg::with_name("consensus").long("consensus").help(
+                            "Checks each block is on its chain consensus, instead of only \
+                            fetching its payload.",
+                        )),
                 ),
         )
         .get_matches();
 
+    let output = matches
+        .value_of("output")
+        .expect("output has default value")
+        .parse()
+        .expect("output mode is validated by clap");
+
+    if let Err(e) = run(&matches, output) {
+        match output {
+            Output::Text => eprintln!("Error: {}", e),
+            Output::Json => eprintln!("{}", e.to_json()),
+        }
+        process::exit(1);
+    }
+}
+
+fn run(matches: &ArgMatches, output: Output) -> Result<(), seresa::Error> {
     let host = matches.value_of("host").unwrap_or("0.0.0.0");
-    let port = matches
-        .value_of("port")
-        .unwrap_or("8330")
-        .parse::<usize>()?;
+    let port: u16 = parse_arg(matches, "port")?.unwrap_or(8330);
 
     if let Some(matches) = matches.subcommand_matches("share") {
         let chain_name = matches
             .value_of("chain")
             .expect("chain name must be defined");
-        let chain_id = ChainId::new(chain_name)?;
+        let chain_id = chain_id(chain_name)?;
 
         let addr = format!("{}:{}", host, port);
         let addr = addr.as_str();
@@ -227,6 +603,23 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         if let Some(matches) = matches.subcommand_matches("post") {
             let signature = matches.value_of("signature");
+
+            if let Some(filename) = matches.value_of("bibtex") {
+                let input = fs::read_to_string(filename)?;
+
+                let mut index = open_index(addr, chain_name)?;
+
+                seresa::import_bibtex(
+                    io::stdout(),
+                    &chain_client,
+                    &mut index,
+                    signature,
+                    &input,
+                    output,
+                )?;
+                return Ok(());
+            }
+
             let title = matches
                 .value_of("title")
                 .expect("article must have a title");
@@ -234,47 +627,155 @@ fn main() -> Result<(), Box<dyn Error>> {
             let authors: Vec<_> = matches.values_of("authors").unwrap_or_default().collect();
             let tags: Vec<_> = matches.values_of("tags").unwrap_or_default().collect();
 
-            seresa::share_article(
+            let mut share = seresa::Share::new(title, &authors, &tags, uri);
+            share.doi = matches.value_of("doi").map(String::from);
+            share.arxiv_id = matches.value_of("arxiv").map(String::from);
+            share.year = parse_arg(matches, "year")?;
+            share.venue = matches.value_of("venue").map(String::from);
+            share.abstract_text = matches.value_of("abstract").map(String::from);
+            share.license = matches.value_of("license").map(String::from);
+            share.language = matches.value_of("language").map(String::from);
+
+            seresa::share_article(io::stdout(), &chain_client, signature, &share, output)?;
+        }
+
+        if let Some(matches) = matches.subcommand_matches("search") {
+            let query = match matches.value_of("query") {
+                Some(query) => query.parse()?,
+                None => {
+                    let strings: Vec<_> =
+                        matches.values_of("strings").unwrap_or_default().collect();
+                    seresa::query::Query::any_of(&strings)
+                }
+            };
+            let min_reps = parse_arg(matches, "min_reps")?;
+            let sort = matches
+                .value_of("sort")
+                .expect("sort has default value")
+                .parse()?;
+            // JSON output defaults to a JSON line per article, unless a format is given.
+            let format = if output == Output::Json && matches.occurrences_of("format") == 0 {
+                seresa::citation::Format::JsonLines
+            } else {
+                matches
+                    .value_of("format")
+                    .expect("format has default value")
+                    .parse()?
+            };
+
+            let mut index = open_index(addr, chain_name)?;
+
+            if matches.is_present("fulltext") {
+                let strings: Vec<_> = matches.values_of("strings").unwrap_or_default().collect();
+
+                seresa::search_text(io::stdout(), &index, &strings, min_reps, sort, format)?;
+            } else if matches.is_present("offline") {
+                seresa::search_index(io::stdout(), &index, &query, min_reps, sort, format)?;
+            } else {
+                seresa::search_all(
+                    io::stdout(),
+                    &chain_client,
+                    &mut index,
+                    &query,
+                    min_reps,
+                    sort,
+                    format,
+                )?;
+            }
+        }
+
+        if let Some(matches) = matches.subcommand_matches("index") {
+            let rebuild = matches.is_present("rebuild");
+            let mut index = open_index(addr, chain_name)?;
+
+            let mut fetch_resource = |uri: &str| {
+                let mut uri: FchsUri = uri.parse()?;
+                if uri.chain.is_none() {
+                    uri.chain = Some(String::from(chain_name));
+                }
+
+                // Resources are only fetched if their text can be extracted, in memory.
+                let options = resource::DownloadOptions::default();
+                match resource::resource_info(&client, &uri, &options)? {
+                    Some(info)
+                        if fulltext::is_supported(&info.mime)
+                            && info.size <= fulltext::MAX_RESOURCE_SIZE => {}
+                    _ => return Ok(None),
+                }
+
+                let mut content = Vec::new();
+                resource::download_resource(&mut content, &client, &uri, &options)?;
+                Ok(Some(content))
+            };
+            let fetch_resource: Option<&mut seresa::index::FetchResource> =
+                if matches.is_present("fulltext") {
+                    Some(&mut fetch_resource)
+                } else {
+                    None
+                };
+
+            seresa::index_chain(
                 io::stdout(),
                 &chain_client,
-                signature,
-                title,
-                &authors,
-                &tags,
-                uri,
+                &mut index,
+                rebuild,
+                fetch_resource,
+                output,
             )?;
         }
 
-        if let Some(matches) = matches.subcommand_matches("search") {
-            let strings: Vec<_> = matches.values_of("strings").unwrap_or_default().collect();
+        if let Some(matches) = matches.subcommand_matches("show") {
+            let hash = matches.value_of("hash").expect("hash must be provided");
+            let output = if matches.is_present("json") {
+                Output::Json
+            } else {
+                output
+            };
 
-            seresa::search_all(io::stdout(), &chain_client, &strings)?;
+            seresa::show_share(io::stdout(), &chain_client, hash, output)?;
         }
 
         if let Some(matches) = matches.subcommand_matches("get-uri") {
             let hash = matches.value_of("hash").expect("hash must be provided");
 
-            seresa::get_uri(io::stdout(), &chain_client, &hash)?;
+            seresa::get_uri(io::stdout(), &chain_client, hash, output)?;
+        }
+
+        if let Some(matches) = matches.subcommand_matches("fetch") {
+            let hash = matches.value_of("hash").expect("hash must be provided");
+            let filepath = matches.value_of("out").unwrap_or("-");
+            let path = Some(Path::new(filepath)).filter(|_| filepath != "-");
+
+            let share = seresa::Share::from_payload(&chain_client.payload(hash, None)?)?;
+            let downloaded = resource::fetch_uri(
+                io::stdout(),
+                path,
+                &client,
+                chain_name,
+                &share.uri,
+                &resource::DownloadOptions::default(),
+            )?;
+
+            report_download(output, &share.uri, path.and(Some(filepath)), &downloaded);
         }
 
         if let Some(matches) = matches.subcommand_matches("get-title") {
             let hash = matches.value_of("hash").expect("hash must be provided");
 
-            seresa::get_title(io::stdout(), &chain_client, &hash)?;
+            seresa::get_title(io::stdout(), &chain_client, hash, output)?;
         }
     }
 
     if let Some(matches) = matches.subcommand_matches("resource") {
         if let Some(matches) = matches.subcommand_matches("upload") {
-            let chain_name = matches
-                .value_of("chain")
-                .expect("chain name must be defined");
-            let chain_id = ChainId::new(chain_name)?;
+            let chain_names: Vec<&str> = matches
+                .values_of("chain")
+                .expect("chain name must be defined")
+                .collect();
 
             let addr = format!("{}:{}", host, port);
             let addr = addr.as_str();
             let client = Client::new(addr);
-            let chain_client = client.chain(&chain_id);
 
             let signature = matches.value_of("signature");
 
@@ -285,26 +786,195 @@ fn main() -> Result<(), Box<dyn Error>> {
             let filename = matches.value_of("file").expect("file must not be empty.");
             let file = File::open(filename)?;
 
-            resource::upload_resource(io::stdout(), file, &chain_client, signature, title)?;
+            // File is hashed once, to find the journals of every chain.
+            let digest = seresa::journal::file_digest(Path::new(filename))?;
+            let mut targets = Vec::new();
+            for chain_name in chain_names {
+                let mut journal = open_journal(addr, chain_name, &digest)?;
+                if !matches.is_present("resume") {
+                    journal.clear()?;
+                }
+
+                targets.push(resource::UploadTarget {
+                    chain_client: client.chain(&chain_id(chain_name)?),
+                    journal,
+                });
+            }
+
+            let options = resource::UploadOptions {
+                signature,
+                title,
+                filename: Path::new(filename).file_name().and_then(|f| f.to_str()),
+                compression: if matches.is_present("compress") {
+                    Some(resource::Compression::Deflate)
+                } else {
+                    None
+                },
+                encoding: matches
+                    .value_of("encoding")
+                    .expect("encoding has default value")
+                    .parse()?,
+                encryption: match (
+                    matches.value_of("passphrase"),
+                    matches.value_of("recipient"),
+                ) {
+                    (Some(passphrase), _) => Some(crypt::EncryptionKey::Passphrase(passphrase)),
+                    (_, Some(recipient)) => Some(crypt::EncryptionKey::Recipient(recipient)),
+                    _ => None,
+                },
+                erasure: matches.value_of("erasure").map(str::parse).transpose()?,
+            };
+
+            resource::upload_resource(io::stdout(), file, &mut targets, &options, output)?;
         }
 
         if let Some(matches) = matches.subcommand_matches("download") {
-            let filepath = matches.value_of("output").unwrap_or("-");
-            let writer: Box<dyn io::Write> = if filepath == "-" {
-                Box::new(io::stdout())
-            } else {
-                Box::new(File::create(filepath)?)
+            let filepath = matches.value_of("out").unwrap_or("-");
+            let uri = matches.value_of("uri").expect("download must have URI");
+            let uri: FchsUri = uri.parse()?;
+
+            let addr = format!("{}:{}", host, port);
+            let addr = addr.as_str();
+            let client = Client::new(addr);
+
+            let options = resource::DownloadOptions {
+                jobs: parse_arg(matches, "jobs")?.unwrap_or(resource::DEFAULT_JOBS),
+                min_reps: if matches.is_present("force") {
+                    None
+                } else {
+                    Some(parse_arg(matches, "min_reps")?.unwrap_or(resource::DEFAULT_MIN_REPS))
+                },
+                consensus: matches.is_present("consensus"),
+                key: match (matches.value_of("passphrase"), matches.value_of("key")) {
+                    (Some(passphrase), _) => Some(crypt::DecryptionKey::Passphrase(passphrase)),
+                    (_, Some(key)) => Some(crypt::DecryptionKey::PrivateKey(key)),
+                    _ => None,
+                },
             };
 
-            let uri = matches.value_of("uri").expect("download must have URI");
+            if filepath == "-" {
+                if matches.is_present("resume") {
+                    return Err(seresa::Error::InputError(String::from(
+                        "'--resume' requires an output file",
+                    )));
+                }
+
+                let downloaded =
+                    resource::download_resource(io::stdout(), &client, &uri, &options)?;
+                report_download(output, &uri.to_string(), None, &downloaded);
+                return Ok(());
+            }
+
+            let resume = matches.is_present("resume");
+            let downloaded =
+                resource::download_file(Path::new(filepath), &client, &uri, resume, &options)?;
+            report_download(output, &uri.to_string(), Some(filepath), &downloaded);
+        }
+
+        if let Some(matches) = matches.subcommand_matches("mirror") {
+            let uri = matches.value_of("from").expect("mirror must have URI");
+            let uri: FchsUri = uri.parse()?;
+            let chain_id = chain_id(matches.value_of("to").expect("mirror must have chain"))?;
 
             let addr = format!("{}:{}", host, port);
             let addr = addr.as_str();
             let client = Client::new(addr);
+            let chain_client = client.chain(&chain_id);
+
+            let options = resource::DownloadOptions {
+                jobs: parse_arg(matches, "jobs")?.unwrap_or(resource::DEFAULT_JOBS),
+                min_reps: if matches.is_present("force") {
+                    None
+                } else {
+                    Some(parse_arg(matches, "min_reps")?.unwrap_or(resource::DEFAULT_MIN_REPS))
+                },
+                consensus: matches.is_present("consensus"),
+                key: None,
+            };
 
-            resource::download_resource(writer, &client, uri)?;
+            let mirror = resource::mirror_resource(
+                io::stdout(),
+                &client,
+                &uri,
+                &chain_client,
+                matches.value_of("signature"),
+                &options,
+                output,
+            )?;
+            output.write_line(io::stdout(), &mirror, &json!({ "uri": mirror.to_string() }))?;
         }
     }
 
     Ok(())
 }
+
+/// Reports download of `uri` to file at `path`, or on Stdout if there is no path. Blocks rebuilt
+/// from parity blocks are reported on Stderr, unless listed on the JSON summary of a file download.
+fn report_download(
+    output: Output,
+    uri: &str,
+    path: Option<&str>,
+    downloaded: &resource::Downloaded,
+) {
+    match (output, path) {
+        (Output::Text, _) => {
+            for post in &downloaded.reconstructed {
+                eprintln!("reconstructed block {}", post);
+            }
+        }
+        (Output::Json, Some(path)) => println!(
+            "{}",
+            json!({
+                "uri": uri,
+                "output": path,
+                "bytes": downloaded.bytes,
+                "reconstructed": downloaded.reconstructed,
+            })
+        ),
+        (Output::Json, None) => {
+            if !downloaded.reconstructed.is_empty() {
+                eprintln!("{}", json!({ "reconstructed": downloaded.reconstructed }));
+            }
+        }
+    }
+}
+
+/// Parses value of argument `name`, if present.
+fn parse_arg<F: FromStr>(matches: &ArgMatches, name: &str) -> Result<Option<F>, seresa::Error> {
+    matches
+        .value_of(name)
+        .map(|v| {
+            v.parse().map_err(|_| {
+                seresa::Error::InputError(format!("invalid value \"{}\" for '{}'", v, name))
+            })
+        })
+        .transpose()
+}
+
+/// Parses chain name.
+fn chain_id(name: &str) -> Result<ChainId, seresa::Error> {
+    ChainId::new(name)
+        .map_err(|_| seresa::Error::InputError(format!("invalid chain name \"{}\"", name)))
+}
+
+/// Opens upload journal of file with SHA-256 `digest` to `chain` from Freechains node at `addr`.
+fn open_journal(
+    addr: &str,
+    chain: &str,
+    digest: &str,
+) -> Result<seresa::journal::Journal, seresa::Error> {
+    let dir = seresa::journal::default_dir().ok_or_else(|| {
+        seresa::Error::InputError(String::from("could not find user's local data directory"))
+    })?;
+
+    seresa::journal::Journal::open(&dir, addr, chain, digest)
+}
+
+/// Opens local index of `chain` from Freechains node at `addr`.
+fn open_index(addr: &str, chain: &str) -> Result<seresa::index::Index, seresa::Error> {
+    let dir = seresa::index::default_dir().ok_or_else(|| {
+        seresa::Error::InputError(String::from("could not find user's cache directory"))
+    })?;
+
+    seresa::index::Index::open(&dir, addr, chain)
+}
@@ -0,0 +1,541 @@
+//! Structured search queries over [Share] posts.
+//!
+//! Queries are made of terms, such as `freenet`, `author:clarke` or `tag:="p2p"`, combined with
+//! `AND`, `OR`, `NOT` and parentheses. Terms next to each other are joined by `AND`, and `AND`
+//! binds tighter than `OR`.
+//!
+//! Terms have the form `[field:][=]value`, where:
+//!
+//! - `field` is one of `title`, `author`, `tag`, `uri` or `year`. Terms without field match title,
+//!   authors and tags.
+//! - `=` requires the whole field to match the value, instead of only containing it.
+//! - `value` is a word or a quoted phrase. Matching is not case-sensitive.
+//!
+//! Year terms accept a year, such as `year:2020`, a comparison, such as `year:>=2000`, or an
+//! inclusive range, such as `year:2000..2010`.
+
+use crate::{Error, Share};
+
+use std::iter::Peekable;
+use std::str::FromStr;
+use std::vec;
+
+/// Query AST.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// Matches shares which match the term.
+    Term(Term),
+
+    /// Matches shares which match every query.
+    And(Vec<Query>),
+
+    /// Matches shares which match any query.
+    Or(Vec<Query>),
+
+    /// Matches shares which do not match the query.
+    Not(Box<Query>),
+}
+
+/// Query term, matching a [Share] field against a pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Term {
+    /// Field to be matched.
+    pub field: Field,
+
+    /// Pattern the field is matched against.
+    pub pattern: Pattern,
+}
+
+/// [Share] field matched by a [Term].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// Title, authors and tags.
+    Any,
+    Title,
+    Author,
+    Tag,
+    Uri,
+    Year,
+}
+
+/// Pattern matched against a [Field].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pattern {
+    /// Field contains the string, ignoring case.
+    Contains(String),
+
+    /// Field is equal to the string, ignoring case.
+    Exact(String),
+
+    /// Field is a number inside the inclusive range. [None] bounds are unlimited.
+    Range(Option<u16>, Option<u16>),
+}
+
+impl Query {
+    /// Creates a term query.
+    pub fn term(field: Field, pattern: Pattern) -> Query {
+        Query::Term(Term { field, pattern })
+    }
+
+    /// Creates a query matching shares whose title, authors or tags contain any of `strings`.
+    pub fn any_of(strings: &[&str]) -> Query {
+        let terms = strings
+            .iter()
+            .map(|&s| Query::term(Field::Any, Pattern::Contains(String::from(s))))
+            .collect();
+
+        Query::Or(terms)
+    }
+
+    /// Checks if `share` matches the query.
+    pub fn matches(&self, share: &Share) -> bool {
+        match self {
+            Query::Term(term) => term.matches(share),
+            Query::And(queries) => queries.iter().all(|q| q.matches(share)),
+            Query::Or(queries) => queries.iter().any(|q| q.matches(share)),
+            Query::Not(query) => !query.matches(share),
+        }
+    }
+
+    /// Scores how relevant `share` is for the query, as the number of matched terms which are not
+    /// negated.
+    pub fn relevance(&self, share: &Share) -> usize {
+        match self {
+            Query::Term(term) => term.matches(share) as usize,
+            Query::And(queries) | Query::Or(queries) => {
+                queries.iter().map(|q| q.relevance(share)).sum()
+            }
+            Query::Not(_) => 0,
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser {
+            tokens: tokens.into_iter().peekable(),
+        };
+
+        let query = parser.or()?;
+        if parser.tokens.peek().is_some() {
+            return Err(query_error("unexpected ')'"));
+        }
+
+        Ok(query)
+    }
+}
+
+impl Term {
+    /// Checks if `share` matches the term.
+    pub fn matches(&self, share: &Share) -> bool {
+        let values: Vec<String> = match self.field {
+            Field::Any => std::iter::once(&share.title)
+                .chain(&share.authors)
+                .chain(&share.tags)
+                .cloned()
+                .collect(),
+            Field::Title => vec![share.title.clone()],
+            Field::Author => share.authors.clone(),
+            Field::Tag => share.tags.clone(),
+            Field::Uri => vec![share.uri.clone()],
+            Field::Year => share.year.iter().map(u16::to_string).collect(),
+        };
+
+        values.iter().any(|v| self.pattern.matches(v))
+    }
+}
+
+impl FromStr for Field {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "title" => Ok(Field::Title),
+            "author" => Ok(Field::Author),
+            "tag" => Ok(Field::Tag),
+            "uri" => Ok(Field::Uri),
+            "year" => Ok(Field::Year),
+            _ => Err(query_error(&format!("unknown field \"{}\"", s))),
+        }
+    }
+}
+
+impl Pattern {
+    /// Checks if `value` matches the pattern.
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            Pattern::Contains(s) => value.to_lowercase().contains(&s.to_lowercase()),
+            Pattern::Exact(s) => value.to_lowercase() == s.to_lowercase(),
+            Pattern::Range(min, max) => match value.parse::<u16>() {
+                Ok(n) => min.map(|m| n >= m).unwrap_or(true) && max.map(|m| n <= m).unwrap_or(true),
+                Err(_) => false,
+            },
+        }
+    }
+
+    /// Parses a year pattern, such as `2020`, `>=2000` or `2000..2010`.
+    fn year(value: &str) -> Result<Pattern, Error> {
+        let parse = |y: &str| {
+            y.parse::<u16>()
+                .map_err(|_| query_error(&format!("invalid year \"{}\"", y)))
+        };
+
+        let pattern = if let Some((min, max)) = value.split_once("..") {
+            let min = if min.is_empty() {
+                None
+            } else {
+                Some(parse(min)?)
+            };
+            let max = if max.is_empty() {
+                None
+            } else {
+                Some(parse(max)?)
+            };
+            Pattern::Range(min, max)
+        } else if let Some(y) = value.strip_prefix(">=") {
+            Pattern::Range(Some(parse(y)?), None)
+        } else if let Some(y) = value.strip_prefix("<=") {
+            Pattern::Range(None, Some(parse(y)?))
+        } else if let Some(y) = value.strip_prefix('>') {
+            Pattern::Range(Some(parse(y)?.saturating_add(1)), None)
+        } else if let Some(y) = value.strip_prefix('<') {
+            Pattern::Range(None, Some(parse(y)?.saturating_sub(1)))
+        } else {
+            let y = parse(value)?;
+            Pattern::Range(Some(y), Some(y))
+        };
+
+        Ok(pattern)
+    }
+}
+
+fn query_error(msg: &str) -> Error {
+    Error::InputError(format!("invalid query: {}", msg))
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Term(Term),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == '(' || c == ')' {
+            chars.next();
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            continue;
+        }
+
+        // Field prefix, only if followed by ':' and a known field name.
+        let rest = &s[start..];
+        let prefix_len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let field = if rest[prefix_len..].starts_with(':') {
+            rest[..prefix_len].parse::<Field>().ok()
+        } else {
+            None
+        };
+        if field.is_some() {
+            for _ in 0..=prefix_len {
+                chars.next();
+            }
+        }
+
+        let exact = chars.peek().map(|&(_, c)| c == '=').unwrap_or(false);
+        if exact {
+            chars.next();
+        }
+
+        let quoted = chars.peek().map(|&(_, c)| c == '"').unwrap_or(false);
+        let mut value = String::new();
+        if quoted {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, c)) => value.push(c),
+                    None => return Err(query_error("unclosed '\"'")),
+                }
+            }
+        } else {
+            while let Some(&(_, c)) = chars.peek() {
+                if c.is_whitespace() || c == '(' || c == ')' {
+                    break;
+                }
+                value.push(c);
+                chars.next();
+            }
+        }
+
+        if field.is_none() && !exact && !quoted {
+            let keyword = match value.as_str() {
+                "AND" => Some(Token::And),
+                "OR" => Some(Token::Or),
+                "NOT" => Some(Token::Not),
+                _ => None,
+            };
+            if let Some(keyword) = keyword {
+                tokens.push(keyword);
+                continue;
+            }
+        }
+
+        if value.is_empty() {
+            return Err(query_error("empty term"));
+        }
+
+        let field = field.unwrap_or(Field::Any);
+        let pattern = match field {
+            Field::Year if !exact => Pattern::year(&value)?,
+            _ if exact => Pattern::Exact(value),
+            _ => Pattern::Contains(value),
+        };
+        tokens.push(Token::Term(Term { field, pattern }));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Peekable<vec::IntoIter<Token>>,
+}
+
+impl Parser {
+    fn peek(&mut self) -> Option<&Token> {
+        self.tokens.peek()
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        self.tokens.next()
+    }
+
+    fn or(&mut self) -> Result<Query, Error> {
+        let mut queries = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            queries.push(self.and()?);
+        }
+
+        Ok(flatten(queries, Query::Or))
+    }
+
+    fn and(&mut self) -> Result<Query, Error> {
+        let mut queries = vec![self.not()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                }
+                Some(Token::Open) | Some(Token::Not) | Some(Token::Term(_)) => {}
+                _ => break,
+            }
+            queries.push(self.not()?);
+        }
+
+        Ok(flatten(queries, Query::And))
+    }
+
+    fn not(&mut self) -> Result<Query, Error> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            let query = self.not()?;
+            return Ok(Query::Not(Box::new(query)));
+        }
+
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Query, Error> {
+        match self.next() {
+            Some(Token::Open) => {
+                let query = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(query),
+                    _ => Err(query_error("missing ')'")),
+                }
+            }
+            Some(Token::Term(term)) => Ok(Query::Term(term)),
+            Some(Token::Close) => Err(query_error("unexpected ')'")),
+            Some(_) => Err(query_error("misplaced operator")),
+            None => Err(query_error("unexpected end of query")),
+        }
+    }
+}
+
+/// Joins `queries` with `op`, unless there is a single query.
+fn flatten(mut queries: Vec<Query>, op: fn(Vec<Query>) -> Query) -> Query {
+    if queries.len() == 1 {
+        queries.remove(0)
+    } else {
+        op(queries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(query: &str) -> Query {
+        query.parse().unwrap()
+    }
+
+    fn contains(field: Field, value: &str) -> Query {
+        Query::term(field, Pattern::Contains(String::from(value)))
+    }
+
+    fn any(value: &str) -> Query {
+        contains(Field::Any, value)
+    }
+
+    fn share(year: Option<u16>) -> Share {
+        let mut share = Share::new(
+            "Freenet: A Distributed Anonymous Information Storage",
+            &["Ian Clarke", "Oskar Sandberg"],
+            &["p2p", "anonymity"],
+            "http://example.site/freenet.pdf",
+        );
+        share.year = year;
+        share
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            parse("a b OR c AND d"),
+            Query::Or(vec![
+                Query::And(vec![any("a"), any("b")]),
+                Query::And(vec![any("c"), any("d")]),
+            ])
+        );
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        assert_eq!(
+            parse("a AND (b OR c)"),
+            Query::And(vec![any("a"), Query::Or(vec![any("b"), any("c")])])
+        );
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and() {
+        assert_eq!(
+            parse("NOT a b"),
+            Query::And(vec![Query::Not(Box::new(any("a"))), any("b")])
+        );
+        assert_eq!(
+            parse("NOT NOT a"),
+            Query::Not(Box::new(Query::Not(Box::new(any("a")))))
+        );
+    }
+
+    #[test]
+    fn parses_fields_and_quoted_phrases() {
+        assert_eq!(
+            parse(r#"author:"ian clarke" title:freenet"#),
+            Query::And(vec![
+                contains(Field::Author, "ian clarke"),
+                contains(Field::Title, "freenet"),
+            ])
+        );
+        assert_eq!(parse(r#""NOT""#), any("NOT"));
+        assert_eq!(parse("other:value"), any("other:value"));
+    }
+
+    #[test]
+    fn parses_exact_terms() {
+        assert_eq!(
+            parse(r#"tag:="p2p" =freenet"#),
+            Query::And(vec![
+                Query::term(Field::Tag, Pattern::Exact(String::from("p2p"))),
+                Query::term(Field::Any, Pattern::Exact(String::from("freenet"))),
+            ])
+        );
+        assert_eq!(
+            parse("year:=2020"),
+            Query::term(Field::Year, Pattern::Exact(String::from("2020")))
+        );
+    }
+
+    #[test]
+    fn parses_year_ranges() {
+        let year = |q: &str| match parse(q) {
+            Query::Term(Term {
+                field: Field::Year,
+                pattern,
+            }) => pattern,
+            q => panic!("not a year term: {:?}", q),
+        };
+
+        assert_eq!(year("year:2020"), Pattern::Range(Some(2020), Some(2020)));
+        assert_eq!(year("year:>=2000"), Pattern::Range(Some(2000), None));
+        assert_eq!(year("year:>2000"), Pattern::Range(Some(2001), None));
+        assert_eq!(year("year:<=2010"), Pattern::Range(None, Some(2010)));
+        assert_eq!(year("year:<2010"), Pattern::Range(None, Some(2009)));
+        assert_eq!(
+            year("year:2000..2010"),
+            Pattern::Range(Some(2000), Some(2010))
+        );
+        assert_eq!(year("year:..2010"), Pattern::Range(None, Some(2010)));
+        assert_eq!(year("year:2000.."), Pattern::Range(Some(2000), None));
+    }
+
+    #[test]
+    fn matches_shares() {
+        let share = share(Some(2001));
+
+        assert!(parse("FREENET").matches(&share));
+        assert!(parse("author:sandberg tag:p2p").matches(&share));
+        assert!(!parse("author:sandberg NOT tag:p2p").matches(&share));
+        assert!(parse("tag:=P2P").matches(&share));
+        assert!(!parse("tag:=p2").matches(&share));
+        assert!(parse(r#"title:"anonymous information""#).matches(&share));
+        assert!(parse("year:2000..2005").matches(&share));
+        assert!(!parse("year:>2001").matches(&share));
+        assert!(!parse("year:2001").matches(&self::share(None)));
+        assert!(parse("uri:example.site OR nothing").matches(&share));
+    }
+
+    #[test]
+    fn scores_relevance_without_negated_terms() {
+        let share = share(None);
+
+        assert_eq!(parse("freenet OR clarke OR nothing").relevance(&share), 2);
+        assert_eq!(parse("freenet NOT nothing").relevance(&share), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_queries() {
+        for query in &[
+            "",
+            "a AND",
+            "OR a",
+            "NOT",
+            "(a",
+            "a)",
+            "()",
+            r#"title:"unclosed"#,
+            "title:",
+            "year:twenty",
+            "year:2000..x",
+        ] {
+            assert!(query.parse::<Query>().is_err(), "{:?} was parsed", query);
+        }
+    }
+}
@@ -1,159 +1,2350 @@
+use crate::crypt::{self, BlockCipher, DecryptionKey, Encryption, EncryptionKey};
 use freechains::{ChainClient, ChainId, Client, Connect};
+use reed_solomon_erasure::galois_8::ReedSolomon;
 use serde::{Deserialize, Serialize};
-use seresa::Error::{InputError, InvalidContentError, LowReputationError};
+use serde_json::json;
+use seresa::journal::Journal;
+use seresa::uri::FchsUri;
+use seresa::Error::{
+    ConsensusError, DecryptionError, FreechainsError, InputError, IntegrityError,
+    InvalidContentError, LowReputationError,
+};
+use seresa::Output;
+use sha2::{Digest, Sha256};
 
+use flate2::read::DeflateEncoder;
+use flate2::write::DeflateDecoder;
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
 use std::io::prelude::*;
-use std::io::BufReader;
+use std::io::{self, BufReader, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Condvar, Mutex, PoisonError};
+use std::thread;
 
-const CONTENT_BLOCK_SIZE: usize = 80 * 1024;
+/// Maximum post payload size accepted by Freechains nodes, in bytes.
+const MAX_PAYLOAD_SIZE: usize = 128_000;
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Length of the widest `prev` link, relative to a post of the largest height.
+const MAX_PREV_LEN: usize = "fchs:".len() + 20 + "_".len() + 64;
+
+/// Default number of threads fetching resource blocks.
+pub const DEFAULT_JOBS: usize = 4;
+
+/// Default minimum reputation of downloaded blocks.
+pub const DEFAULT_MIN_REPS: isize = -3;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 struct Resource {
     /// Resource title.
     title: String,
 
-    /// Base64 representation of the resource block.
+    /// Text representation of the resource block, on the block `encoding`.
     content: String,
 
+    /// Encoding of the block content, [None] on blocks posted before encodings existed, which are
+    /// encoded on [Base64](Encoding::Base64).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encoding: Option<Encoding>,
+
     /// Previous block of the resource, [None] if firsr block.
     prev: Option<String>,
+
+    /// Hex encoded SHA-256 digest of the block content, [None] on blocks posted before digests
+    /// existed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+
+    /// Encryption of the resource, [None] if not encrypted. Present on every post of encrypted
+    /// resources, including the manifest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encryption: Option<Encryption>,
+
+    /// Hex encoded nonce of the encrypted block content, [None] if not encrypted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nonce: Option<String>,
+
+    /// Hashes of resource blocks, in order, only present on index posts, which hold the block
+    /// list of manifests too large to fit on a post. Index posts have empty content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    index: Option<Vec<String>>,
+
+    /// Data blocks of the stripe, in order, only present on parity posts of erasure coded
+    /// resources, whose content is a parity block of the stored contents of the stripe.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stripe: Option<Vec<Shard>>,
+
+    /// Resource manifest, only present on the manifest post, which is posted after the last
+    /// block, with empty content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    manifest: Option<Manifest>,
 }
 
-pub fn upload_resource<T>(
+/// Data block of an erasure coded stripe, as stored, described so it can be rebuilt.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Shard {
+    /// Length of the stored block content, in bytes.
+    size: usize,
+
+    /// Hex encoded SHA-256 digest of the stored block content.
+    sha256: String,
+
+    /// Hex encoded nonce of the encrypted block content, [None] if not encrypted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nonce: Option<String>,
+}
+
+impl Shard {
+    /// Describes block `resource`, with `stored` content.
+    fn of(stored: &[u8], resource: &Resource) -> Shard {
+        Shard {
+            size: stored.len(),
+            sha256: format!("{:x}", Sha256::digest(stored)),
+            nonce: resource.nonce.clone(),
+        }
+    }
+}
+
+/// Description of a whole resource, used to verify downloads.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Manifest {
+    /// Hashes of the resource blocks, in order. Empty if the blocks are listed on index posts. On
+    /// erasure coded resources, each stripe of data blocks is followed by its parity blocks.
+    blocks: Vec<String>,
+
+    /// Hashes of the index posts listing the resource blocks, in order, if the block list does
+    /// not fit on the manifest post.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    indexes: Vec<String>,
+
+    /// URIs of the manifests of copies of the resource on other chains, cut on the same blocks,
+    /// whose blocks are fetched when a block of this copy cannot be.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    alternates: Vec<String>,
+
+    /// Erasure coding of the blocks, [None] if there are no parity blocks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    erasure: Option<Erasure>,
+
+    /// Resource length, in bytes, after decompression.
+    size: u64,
+
+    /// Hex encoded SHA-256 digest of the resource, after decompression.
+    sha256: String,
+
+    /// Name of the uploaded file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    filename: Option<String>,
+
+    /// MIME type of the resource.
+    mime: String,
+
+    /// Compression applied to the resource before it was cut in blocks, [None] if not compressed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    compression: Option<Compression>,
+}
+
+/// Text encoding of resource blocks contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    /// Base64, with 33% of overhead.
+    Base64,
+
+    /// [Z85](https://rfc.zeromq.org/spec/32/), with 25% of overhead and no characters escaped on
+    /// JSON strings. Tail bytes are padded as done by the `z85` crate.
+    Z85,
+}
+
+/// Erasure coding of a resource, where each stripe of `data` blocks is followed by `parity` parity
+/// blocks, so the stripe is recovered from any `data` of its blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Erasure {
+    /// Number of data blocks of each stripe, fewer on the last stripe.
+    pub data: usize,
+
+    /// Number of parity blocks of each stripe.
+    pub parity: usize,
+}
+
+/// Compression codec of a resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// Raw [DEFLATE](https://www.rfc-editor.org/rfc/rfc1951) stream.
+    Deflate,
+}
+
+/// Parameters of a resource upload.
+pub struct UploadOptions<'a> {
+    /// User's private key, used to sign the posts.
+    pub signature: Option<&'a str>,
+
+    /// Resource title.
+    pub title: &'a str,
+
+    /// Name of the uploaded file, recorded on the manifest and used to guess the resource MIME
+    /// type.
+    pub filename: Option<&'a str>,
+
+    /// Compression applied to the resource before it is cut in blocks.
+    pub compression: Option<Compression>,
+
+    /// Encoding of the blocks contents.
+    pub encoding: Encoding,
+
+    /// Key the blocks contents are encrypted with, [None] if not encrypted.
+    pub encryption: Option<EncryptionKey<'a>>,
+
+    /// Erasure coding of the blocks, [None] if no parity blocks are posted.
+    pub erasure: Option<Erasure>,
+}
+
+/// Chain a resource is uploaded to, with the upload journal of the resource to that chain.
+pub struct UploadTarget<'a, T> {
+    /// Client of the chain the blocks are posted to.
+    pub chain_client: ChainClient<'a, T>,
+
+    /// Journal recording the blocks already posted to the chain.
+    pub journal: Journal,
+}
+
+/// Posts the block list of `manifest` on index posts, as large as possible, printing the hash of
+/// each post. Index posts have the title and encryption of the `manifest_post`, and are linked by
+/// `prev` after its previous post. Returns hashes of the index posts, in order, and the URI of the
+/// last one.
+fn post_indexes<T>(
+    mut w: impl Write,
+    chain_client: &ChainClient<T>,
+    manifest_post: &Resource,
+    manifest: &Manifest,
+    signature: Option<&str>,
+    output: Output,
+) -> Result<(Vec<String>, String), seresa::Error>
+where
+    T: Connect,
+{
+    let mut indexes = Vec::new();
+    let mut prev = manifest_post.prev.clone();
+    let mut blocks = manifest.blocks.as_slice();
+    while !blocks.is_empty() {
+        let mut resource = Resource {
+            prev,
+            index: Some(Vec::new()),
+            stripe: None,
+            manifest: None,
+            ..manifest_post.clone()
+        };
+
+        // Each hash takes its length, quotes and a comma.
+        let mut size = serde_json::to_vec(&resource)?.len();
+        let mut n = 0;
+        while n < blocks.len() && size + blocks[n].len() + 3 <= MAX_PAYLOAD_SIZE {
+            size += blocks[n].len() + 3;
+            n += 1;
+        }
+        if n == 0 {
+            return Err(InputError(String::from(
+                "resource title is too long to fit on an index post",
+            )));
+        }
+        resource.index = Some(blocks[..n].to_vec());
+        blocks = &blocks[n..];
+
+        let payload = serde_json::to_vec(&resource)?;
+        let hash = chain_client.post(signature, false, &payload)?;
+        output.write_line(
+            &mut w,
+            &hash,
+            &json!({ "index": indexes.len(), "hash": hash }),
+        )?;
+
+        prev = Some(FchsUri::relative(&hash).to_string());
+        indexes.push(hash);
+    }
+
+    Ok((indexes, prev.expect("manifest has at least one block")))
+}
+
+/// Posts manifest post `resource`, listing its blocks on index posts first if the block list does
+/// not fit on the manifest post. Returns the manifest post hash.
+fn post_manifest<T>(
     mut w: impl Write,
-    r: impl Read,
     chain_client: &ChainClient<T>,
+    mut resource: Resource,
     signature: Option<&str>,
-    title: &str,
+    output: Output,
+) -> Result<String, seresa::Error>
+where
+    T: Connect,
+{
+    let mut payload = serde_json::to_vec(&resource)?;
+    if payload.len() > MAX_PAYLOAD_SIZE {
+        let manifest = resource
+            .manifest
+            .take()
+            .expect("manifest post has a manifest");
+        let (indexes, prev) = post_indexes(
+            &mut w,
+            chain_client,
+            &resource,
+            &manifest,
+            signature,
+            output,
+        )?;
+
+        resource.prev = Some(prev);
+        resource.manifest = Some(Manifest {
+            blocks: Vec::new(),
+            indexes,
+            ..manifest
+        });
+        payload = serde_json::to_vec(&resource)?;
+        if payload.len() > MAX_PAYLOAD_SIZE {
+            return Err(InputError(String::from(
+                "resource has too many blocks to be listed on its manifest",
+            )));
+        }
+    }
+
+    Ok(chain_client.post(signature, false, &payload)?)
+}
+
+/// Parameters of a resource download.
+pub struct DownloadOptions<'a> {
+    /// Number of threads fetching the blocks listed on a manifest.
+    pub jobs: usize,
+
+    /// Minimum reputation of each block, [None] to download blocks regardless of their reputation.
+    pub min_reps: Option<isize>,
+
+    /// Whether each block must be on its chain consensus, instead of only having its payload
+    /// fetched.
+    pub consensus: bool,
+
+    /// Key the resource is decrypted with, [None] if not encrypted.
+    pub key: Option<DecryptionKey<'a>>,
+}
+
+impl Default for DownloadOptions<'_> {
+    fn default() -> Self {
+        DownloadOptions {
+            jobs: DEFAULT_JOBS,
+            min_reps: Some(DEFAULT_MIN_REPS),
+            consensus: false,
+            key: None,
+        }
+    }
+}
+
+/// Uploads resource read from `r` as a list of blocks, followed by its manifest, printing the hash
+/// of each post. The manifest hash is the one to be used on the resource URI.
+///
+/// Blocks are as large as possible, so each encoded block post just fits on the maximum post size
+/// of Freechains nodes.
+///
+/// Encrypted blocks hold the blocks contents encrypted after compression, and the manifest of an
+/// encrypted resource has no filename.
+///
+/// Each block is posted to every chain of `targets`, so each chain holds a complete copy of the
+/// resource, with its own manifest. The manifest of the first chain, posted last, lists the
+/// manifests of the other copies as alternates, which downloads fall back to.
+///
+/// Every posted block and manifest is recorded on the upload journal of its chain. Posts already
+/// recorded are not posted again, so an interrupted upload continues from the last posted block
+/// or manifest. Journals are removed once the manifests are posted.
+pub fn upload_resource<T>(
+    mut w: impl Write,
+    r: impl Read,
+    targets: &mut [UploadTarget<T>],
+    options: &UploadOptions,
+    output: Output,
 ) -> Result<(), seresa::Error>
 where
     T: Connect,
 {
-    let mut reader = BufReader::new(r);
+    if targets.is_empty() {
+        return Err(InputError(String::from(
+            "resource must be uploaded to at least one chain",
+        )));
+    }
+    let mut chains = HashSet::new();
+    if let Some(t) = targets
+        .iter()
+        .find(|t| !chains.insert(t.chain_client.name()))
+    {
+        return Err(InputError(format!(
+            "resource cannot be uploaded twice to chain \"{}\"",
+            t.chain_client.name()
+        )));
+    }
 
-    let mut prev: Option<String> = None;
+    let format = format!(
+        "{};{};{};{};{};{}",
+        options.encoding.name(),
+        MAX_PAYLOAD_SIZE,
+        MAX_PREV_LEN,
+        options.compression.map_or("none", Compression::name),
+        options
+            .encryption
+            .as_ref()
+            .map_or("none", EncryptionKey::name),
+        options
+            .erasure
+            .map_or_else(|| String::from("none"), |e| e.to_string())
+    );
+    if targets
+        .iter()
+        .any(|t| t.journal.format().is_some_and(|f| f != format))
+    {
+        return Err(InputError(String::from(
+            "upload journal was recorded with other upload options, upload must be started over",
+        )));
+    }
+
+    // Blocks already posted were encrypted with a key which must be used again, on every chain.
+    // Journals only record the public encryption header, so the key is derived again.
+    let mut recorded = targets.iter().filter_map(|t| t.journal.encryption());
+    let first = recorded.next().map(String::from);
+    if recorded.any(|e| Some(e) != first.as_deref()) {
+        return Err(InputError(String::from(
+            "upload journals were recorded with different encryption keys, upload must be started \
+            over",
+        )));
+    }
+    let cipher = match (&options.encryption, first) {
+        (None, _) => None,
+        (Some(key), Some(recorded)) => Some(resume_cipher(targets, &recorded, key)?),
+        (Some(key), None) => Some(BlockCipher::new(key)?),
+    };
+    if let Some(cipher) = &cipher {
+        let recorded = serde_json::to_string(cipher.encryption())?;
+        for target in targets.iter_mut() {
+            target.journal.set_encryption(Some(recorded.clone()));
+        }
+    }
+    let encryption = cipher.as_ref().map(|c| c.encryption().clone());
+
+    let template = Resource {
+        title: String::from(options.title),
+        content: String::new(),
+        encoding: Some(options.encoding),
+        prev: None,
+        sha256: Some(format!("{:x}", Sha256::digest([]))),
+        encryption: encryption.clone(),
+        nonce: cipher.as_ref().map(|_| "0".repeat(crypt::NONCE_LEN)),
+        index: None,
+        stripe: None,
+        manifest: None,
+    };
+
+    // Blocks have the same capacity on every chain, so copies are cut the same.
+    let tag_size = cipher.as_ref().map_or(0, |_| crypt::TAG_SIZE);
+    let mut capacity = block_capacity(&template, options.encoding, tag_size)?;
+    if let Some(erasure) = options.erasure {
+        // Parity blocks are as long as the longest stored block of their stripe.
+        let shard = Shard {
+            size: usize::MAX,
+            sha256: "0".repeat(64),
+            nonce: template.nonce.clone(),
+        };
+        let parity = Resource {
+            nonce: None,
+            stripe: Some(vec![shard; erasure.data]),
+            ..template.clone()
+        };
+        capacity = capacity.min(block_capacity(&parity, options.encoding, tag_size)?);
+    }
+    let (data_count, parity_count) = options.erasure.map_or((1, 0), |e| (e.data, e.parity));
+
+    let mut reader = encoder(HashReader::new(BufReader::new(r)), options.compression);
+
+    // Last data block posted to each chain, the `prev` of the next posts.
+    let mut prevs: Vec<Option<String>> = vec![None; targets.len()];
+    let mut stored_size = 0;
+    let mut block = 0;
+    let mut parity = 0;
+    let mut position = 0;
     let mut end = false;
     while !end {
-        let mut buf = [0; CONTENT_BLOCK_SIZE];
-        let mut bytes_read = 0;
-        while bytes_read < CONTENT_BLOCK_SIZE {
-            let n = reader.read(&mut buf[bytes_read..])?;
-            bytes_read += n;
+        let mut contents = Vec::new();
+        while !end && contents.len() < data_count {
+            let mut buf = vec![0; capacity];
+            let mut bytes_read = 0;
+            while bytes_read < capacity {
+                let n = reader.read(&mut buf[bytes_read..])?;
+                bytes_read += n;
 
-            if n == 0 {
-                end = true;
-                break;
+                if n == 0 {
+                    end = true;
+                    break;
+                }
             }
+            buf.truncate(bytes_read);
+            stored_size += bytes_read;
+            contents.push(buf);
         }
 
-        let title = String::from(title);
-        let content = base64::encode(&buf[..bytes_read]);
-        let resource = Resource {
-            title,
-            content,
-            prev,
-        };
+        // Blocks are encrypted once, when first posted, and shared by every chain.
+        let mut sealed: Vec<Option<(Vec<u8>, Resource)>> = vec![None; contents.len()];
+        for (t, target) in targets.iter_mut().enumerate() {
+            let chain = Some(target.chain_client.name()).filter(|_| t > 0);
 
-        let payload = serde_json::to_vec(&resource)?;
-        let hash = chain_client.post(signature, false, &payload)?;
-        writeln!(w, "{}", hash)?;
+            // Stored contents of the stripe blocks, [None] for blocks posted before.
+            let mut stripe = Vec::new();
+            for (j, content) in contents.iter().enumerate() {
+                let hash = match target.journal.blocks().get(position + j) {
+                    Some(hash) => {
+                        stripe.push((hash.clone(), None));
+                        hash.clone()
+                    }
+                    None => {
+                        let (stored, resource) = sealed[j].get_or_insert_with(|| {
+                            seal_block(&template, content, options.encoding, cipher.as_ref())
+                        });
+                        let resource = Resource {
+                            // Blocks already posted are read again, so they must have the same prev.
+                            prev: prevs[t]
+                                .as_deref()
+                                .map(|h| FchsUri::relative(h).to_string()),
+                            ..resource.clone()
+                        };
+
+                        let payload = serde_json::to_vec(&resource)?;
+                        let hash = target
+                            .chain_client
+                            .post(options.signature, false, &payload)?;
+                        target.journal.record(&format, &hash)?;
+
+                        let shard = Shard::of(stored, &resource);
+                        stripe.push((hash.clone(), Some((stored.clone(), shard))));
+                        hash
+                    }
+                };
+                write_post(&mut w, output, chain, "block", block + j, &hash)?;
+                prevs[t] = Some(hash);
+            }
+
+            let start = position + contents.len();
+            let mut posted: Vec<Option<String>> = (0..parity_count)
+                .map(|j| target.journal.blocks().get(start + j).cloned())
+                .collect();
+            if posted.iter().any(Option::is_none) {
+                let stripe = stripe
+                    .into_iter()
+                    .map(|(hash, shard)| match shard {
+                        Some(shard) => Ok(shard),
+                        None => fetch_shard(&target.chain_client, &hash),
+                    })
+                    .collect::<Result<Vec<_>, seresa::Error>>()?;
+
+                for (j, resource) in parity_blocks(&template, &stripe, parity_count)?
+                    .into_iter()
+                    .enumerate()
+                {
+                    if posted[j].is_some() {
+                        continue;
+                    }
+
+                    let resource = Resource {
+                        prev: prevs[t]
+                            .as_deref()
+                            .map(|h| FchsUri::relative(h).to_string()),
+                        ..resource
+                    };
+                    let payload = serde_json::to_vec(&resource)?;
+                    let hash = target
+                        .chain_client
+                        .post(options.signature, false, &payload)?;
+                    target.journal.record(&format, &hash)?;
+                    posted[j] = Some(hash);
+                }
+            }
+            for (j, hash) in posted.iter().flatten().enumerate() {
+                write_post(&mut w, output, chain, "parity", parity + j, hash)?;
+            }
+        }
+
+        block += contents.len();
+        parity += parity_count;
+        position += contents.len() + parity_count;
+    }
+
+    // Resource length and digest, before compression.
+    let (size, digest) = reader.get_ref().finish();
+
+    if options.compression.is_some() {
+        let saved = (1000.0 - 1000.0 * stored_size as f64 / size.max(1) as f64).round() / 10.0;
+        output.write_line(
+            &mut w,
+            format_args!(
+                "compressed {} bytes to {} bytes, {:.1}% saved",
+                size, stored_size, saved
+            ),
+            &json!({ "size": size, "compressed_size": stored_size, "saved": saved }),
+        )?;
+    }
+
+    // Filenames of encrypted resources are not revealed.
+    let filename = options.filename.filter(|_| cipher.is_none());
+    let mime = filename
+        .map(|f| mime_guess::from_path(f).first_or_octet_stream())
+        .unwrap_or(mime_guess::mime::APPLICATION_OCTET_STREAM);
+    let manifest = Manifest {
+        blocks: Vec::new(),
+        indexes: Vec::new(),
+        alternates: Vec::new(),
+        erasure: options.erasure,
+        size,
+        sha256: digest,
+        filename: filename.map(String::from),
+        mime: mime.to_string(),
+        compression: options.compression,
+    };
+    let resource = Resource {
+        title: String::from(options.title),
+        content: String::new(),
+        encoding: None,
+        prev: None,
+        sha256: None,
+        encryption,
+        nonce: None,
+        index: None,
+        stripe: None,
+        manifest: None,
+    };
+
+    // Copies on the other chains are complete resources, listed as alternates of the first one.
+    // Manifests posted before the upload was interrupted are not posted again.
+    let mut alternates = Vec::new();
+    for (target, prev) in targets.iter_mut().zip(&prevs).skip(1) {
+        let chain = target.chain_client.name();
+        let hash = match target.journal.manifest() {
+            Some(hash) => String::from(hash),
+            None => {
+                let resource = Resource {
+                    prev: prev.as_deref().map(|h| FchsUri::relative(h).to_string()),
+                    manifest: Some(Manifest {
+                        blocks: target.journal.blocks().to_vec(),
+                        ..manifest.clone()
+                    }),
+                    ..resource.clone()
+                };
+
+                let hash = post_manifest(
+                    &mut w,
+                    &target.chain_client,
+                    resource,
+                    options.signature,
+                    output,
+                )?;
+                target.journal.record_manifest(&hash)?;
+                hash
+            }
+        };
+        let uri = FchsUri::new(chain, &hash);
+        output.write_line(&mut w, &uri, &json!({ "manifest": hash, "chain": chain }))?;
+        alternates.push(uri.to_string());
+    }
 
-        prev = Some(format!("fchs:{}", hash));
+    let target = &targets[0];
+    let resource = Resource {
+        prev: prevs[0]
+            .as_deref()
+            .map(|h| FchsUri::relative(h).to_string()),
+        manifest: Some(Manifest {
+            blocks: target.journal.blocks().to_vec(),
+            alternates,
+            ..manifest
+        }),
+        ..resource
+    };
+    let hash = post_manifest(
+        &mut w,
+        &target.chain_client,
+        resource,
+        options.signature,
+        output,
+    )?;
+    for target in targets.iter() {
+        target.journal.remove()?;
     }
+    output.write_line(&mut w, &hash, &json!({ "manifest": hash }))?;
 
     Ok(())
 }
 
+/// Prints `hash` of the `n`-th post of `kind`, such as `block`, posted to `chain`, which is [None]
+/// for the first chain of an upload.
+fn write_post(
+    w: impl Write,
+    output: Output,
+    chain: Option<&str>,
+    kind: &str,
+    n: usize,
+    hash: &str,
+) -> Result<(), seresa::Error> {
+    let mut value = json!({ "hash": hash });
+    value[kind] = json!(n);
+
+    match chain {
+        None => output.write_line(w, hash, &value),
+        Some(chain) => {
+            value["chain"] = json!(chain);
+            output.write_line(w, FchsUri::new(chain, hash), &value)
+        }
+    }
+}
+
+/// Opens cipher of an upload resumed on `targets`, whose journals recorded `encryption` header,
+/// deriving again the cipher key from `key`. The key is checked against the first block posted.
+///
+/// Uploads encrypted to a recipient cannot be resumed, since their ephemeral secret key is not
+/// recorded.
+fn resume_cipher<T>(
+    targets: &[UploadTarget<T>],
+    encryption: &str,
+    key: &EncryptionKey,
+) -> Result<BlockCipher, seresa::Error>
+where
+    T: Connect,
+{
+    let started_over = |reason: &str| {
+        InputError(format!(
+            "upload journal was recorded {}, upload must be started over",
+            reason
+        ))
+    };
+
+    let encryption: Encryption =
+        serde_json::from_str(encryption).map_err(|_| started_over("by an older version"))?;
+    let cipher = match key {
+        EncryptionKey::Passphrase(passphrase) => {
+            BlockCipher::open(&encryption, &DecryptionKey::Passphrase(passphrase))?
+        }
+        EncryptionKey::Recipient(_) => {
+            return Err(started_over(
+                "encrypted to a recipient, whose ephemeral key is not kept",
+            ))
+        }
+    };
+
+    if let Some(target) = targets.iter().find(|t| !t.journal.blocks().is_empty()) {
+        let post = &target.journal.blocks()[0];
+        let block: Resource = serde_json::from_slice(&target.chain_client.payload(post, None)?)?;
+        let stored = decode_content(target.chain_client.name(), post, &block)?;
+        let nonce = block.nonce.as_deref().unwrap_or_default();
+        if block.encryption.as_ref() != Some(&encryption)
+            || cipher.decrypt(nonce, &stored).is_none()
+        {
+            return Err(started_over("with another encryption key"));
+        }
+    }
+
+    Ok(cipher)
+}
+
+/// Seals `content` as a block post based on `template`, encrypting it with `cipher`, if any.
+/// Returns the content as stored and the block post.
+fn seal_block(
+    template: &Resource,
+    content: &[u8],
+    encoding: Encoding,
+    cipher: Option<&BlockCipher>,
+) -> (Vec<u8>, Resource) {
+    let (nonce, stored) = match cipher {
+        Some(cipher) => {
+            let (nonce, stored) = cipher.encrypt(content);
+            (Some(nonce), stored)
+        }
+        None => (None, content.to_vec()),
+    };
+
+    let resource = Resource {
+        content: encoding.encode(&stored),
+        sha256: Some(format!("{:x}", Sha256::digest(&stored))),
+        nonce,
+        ..template.clone()
+    };
+    (stored, resource)
+}
+
+/// Fetches block `post` posted before, returning its content as stored and its description.
+fn fetch_shard<T>(
+    chain_client: &ChainClient<T>,
+    post: &str,
+) -> Result<(Vec<u8>, Shard), seresa::Error>
+where
+    T: Connect,
+{
+    let block: Resource = serde_json::from_slice(&chain_client.payload(post, None)?)?;
+    let stored = decode_content(chain_client.name(), post, &block)?;
+    let shard = Shard::of(&stored, &block);
+    Ok((stored, shard))
+}
+
+/// Returns the `count` parity block posts, based on `template`, of `stripe`, the stored contents
+/// of the data blocks of a stripe, with their descriptions.
+fn parity_blocks(
+    template: &Resource,
+    stripe: &[(Vec<u8>, Shard)],
+    count: usize,
+) -> Result<Vec<Resource>, seresa::Error> {
+    // Shards must have the same length, so shorter blocks are padded with zeros.
+    let len = stripe
+        .iter()
+        .map(|(c, _)| c.len())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let mut shards: Vec<Vec<u8>> = stripe
+        .iter()
+        .map(|(c, _)| {
+            let mut shard = c.clone();
+            shard.resize(len, 0);
+            shard
+        })
+        .chain((0..count).map(|_| vec![0; len]))
+        .collect();
+    ReedSolomon::new(stripe.len(), count)
+        .and_then(|rs| rs.encode(&mut shards))
+        .map_err(|e| InputError(format!("could not compute parity blocks, {}", e)))?;
+
+    let encoding = template.encoding.unwrap_or(Encoding::Base64);
+    let description: Vec<Shard> = stripe.iter().map(|(_, s)| s.clone()).collect();
+    Ok(shards
+        .split_off(stripe.len())
+        .into_iter()
+        .map(|content| Resource {
+            content: encoding.encode(&content),
+            sha256: Some(format!("{:x}", Sha256::digest(&content))),
+            nonce: None,
+            stripe: Some(description.clone()),
+            ..template.clone()
+        })
+        .collect())
+}
+
+/// Summary of a finished download.
+pub struct Downloaded {
+    /// Number of written bytes.
+    pub bytes: usize,
+
+    /// Hashes of the blocks rebuilt from parity blocks, in order.
+    pub reconstructed: Vec<String>,
+}
+
+/// Size and type of a resource, as described by its manifest.
+pub struct ResourceInfo {
+    /// Resource length, in bytes, after decompression.
+    pub size: u64,
+
+    /// MIME type of the resource.
+    pub mime: String,
+}
+
+/// Posts the `count` parity blocks of `stripe`, based on `template`, as [parity_blocks] returns
+/// them. Returns hashes of the parity posts, in order.
+fn post_parity<T>(
+    chain_client: &ChainClient<T>,
+    template: &Resource,
+    stripe: &[(Vec<u8>, Shard)],
+    count: usize,
+    signature: Option<&str>,
+) -> Result<Vec<String>, seresa::Error>
+where
+    T: Connect,
+{
+    parity_blocks(template, stripe, count)?
+        .iter()
+        .map(|resource| Ok(chain_client.post(signature, false, &serde_json::to_vec(resource)?)?))
+        .collect()
+}
+
+/// Downloads resource ending on post `uri`, writing its contents on `w`. Returns the number of
+/// written bytes, with the blocks rebuilt from parity blocks.
+///
+/// If `uri` references a resource manifest, its blocks are fetched in parallel by `options.jobs`
+/// threads, and the resource length and digest are verified against the manifest. Otherwise,
+/// blocks are found by walking the `prev` links, one at a time. Compressed resources are
+/// decompressed.
+///
+/// Blocks with reputation lower than `options.min_reps` are refused with a
+/// [LowReputationError](seresa::Error::LowReputationError), and, if `options.consensus` is set,
+/// blocks not on their chain consensus are refused with a
+/// [ConsensusError](seresa::Error::ConsensusError).
+///
+/// Each block content is verified against its digest, if any. Verification failures are reported
+/// by an [IntegrityError](seresa::Error::IntegrityError) naming the failed post.
+///
+/// Encrypted resources are decrypted with `options.key`, and blocks which cannot be decrypted are
+/// reported by a [DecryptionError](seresa::Error::DecryptionError).
+///
+/// Missing and untrusted blocks of resources with alternate copies are fetched from the copies,
+/// and those of erasure coded resources are rebuilt from the other blocks of their stripe.
 pub fn download_resource<T>(
-    mut w: impl Write,
+    w: impl Write,
     client: &Client<T>,
-    uri: &str,
-) -> Result<(), seresa::Error>
+    uri: &FchsUri,
+    options: &DownloadOptions,
+) -> Result<Downloaded, seresa::Error>
+where
+    T: Connect + Sync,
+{
+    let head = open_resource(client, uri, options)?;
+
+    let mut decoder = Decoder::new(HashWriter::new(w), head.compression());
+    let reconstructed = fetch_resource(client, &head, options, 0, |_, content| {
+        decoder.write_all(content)?;
+        Ok(())
+    })?;
+
+    Ok(Downloaded {
+        bytes: head.verify(&decoder.finish()?)?,
+        reconstructed,
+    })
+}
+
+/// Reads the manifest of resource ending on post `uri`, without fetching its blocks. Returns
+/// [None] if the resource has no manifest, as resources uploaded before manifests existed.
+pub fn resource_info<T>(
+    client: &Client<T>,
+    uri: &FchsUri,
+    options: &DownloadOptions,
+) -> Result<Option<ResourceInfo>, seresa::Error>
 where
     T: Connect,
 {
-    let (chain, mut post) = parse_uri(uri)?;
-    let mut chain = chain.ok_or(InputError(String::from("missing chains name on URI")))?;
+    let head = open_head(client, uri, options)?;
 
-    let mut rord_content: Vec<(ChainId, String)> = Vec::new();
+    Ok(head.block.manifest.map(|manifest| ResourceInfo {
+        size: manifest.size,
+        mime: manifest.mime,
+    }))
+}
 
-    loop {
-        let chain_id = ChainId::new(&chain).or_else(|c| {
-            Err(InputError(format!(
-                "invalid 'fchs' URI format, invalid chain name \"{}\"",
-                c
-            )))
-        })?;
+/// Downloads resource ending on post `uri` to file at `path`, as [download_resource]. Returns the
+/// number of written bytes, with the blocks rebuilt from parity blocks.
+///
+/// Blocks contents are written to a partial file, next to `path`, which is decoded to `path` once
+/// every block is downloaded and the resource is verified. If `resume` is set and a partial file
+/// of the same resource exists, its blocks are checked against the recorded block sizes and
+/// digests, and only the missing blocks are downloaded.
+pub fn download_file<T>(
+    path: &Path,
+    client: &Client<T>,
+    uri: &FchsUri,
+    resume: bool,
+    options: &DownloadOptions,
+) -> Result<Downloaded, seresa::Error>
+where
+    T: Connect + Sync,
+{
+    let head = open_resource(client, uri, options)?;
 
-        if !client.chains()?.contains(&chain_id) {
-            return Err(InputError(format!(
-                "invalid 'fchs' URI, chain \"{}\" not on Freechains node",
-                chain_id
-            )));
+    let part_path = append_extension(path, "part");
+    let state_path = append_extension(path, "part.json");
+
+    let mut state = PartialDownload {
+        uri: uri.to_string(),
+        blocks: Vec::new(),
+    };
+
+    let previous = match fs::read(&state_path) {
+        Ok(bytes) if resume => serde_json::from_slice::<PartialDownload>(&bytes).ok(),
+        _ => None,
+    };
+    let resumed = match previous {
+        Some(previous) if previous.uri == state.uri => {
+            match OpenOptions::new().read(true).write(true).open(&part_path) {
+                Ok(mut file) => {
+                    // Keeps the blocks whose bytes are intact, dropping anything after them.
+                    let mut size = 0;
+                    for block in previous.blocks {
+                        let mut content = vec![0; block.size];
+                        if file.read_exact(&mut content).is_err()
+                            || format!("{:x}", Sha256::digest(&content)) != block.sha256
+                        {
+                            break;
+                        }
+                        size += block.size as u64;
+                        state.blocks.push(block);
+                    }
+                    file.set_len(size)?;
+                    file.seek(SeekFrom::Start(size))?;
+                    Some(file)
+                }
+                // Partial file was removed, so the download starts over.
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e.into()),
+            }
         }
+        _ => None,
+    };
+    let mut file = match resumed {
+        Some(file) => file,
+        None => OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&part_path)?,
+    };
 
-        let chain_client = client.chain(&chain_id);
-        let reps = chain_client.reputation(&post)?;
-        if reps < -3 {
-            return Err(LowReputationError(chain.clone(), post.clone()));
+    let skip = state.blocks.len();
+    let reconstructed = fetch_resource(client, &head, options, skip, |post, content| {
+        file.write_all(content)?;
+
+        state.blocks.push(PartialBlock {
+            post: String::from(post),
+            size: content.len(),
+            sha256: format!("{:x}", Sha256::digest(content)),
+        });
+        let tmp_path = append_extension(&state_path, "tmp");
+        fs::write(&tmp_path, serde_json::to_vec(&state)?)?;
+        fs::rename(&tmp_path, &state_path)?;
+
+        Ok(())
+    })?;
+
+    file.seek(SeekFrom::Start(0))?;
+    let size = match head.compression() {
+        // Partial file already holds the resource contents.
+        None => {
+            let mut writer = HashWriter::new(io::sink());
+            io::copy(&mut file, &mut writer)?;
+            let size = head.verify(&writer)?;
+
+            file.sync_all()?;
+            fs::rename(&part_path, path)?;
+            size
         }
+        Some(compression) => {
+            let tmp_path = append_extension(path, "tmp");
+            let mut decoder =
+                Decoder::new(HashWriter::new(File::create(&tmp_path)?), Some(compression));
+            io::copy(&mut file, &mut decoder)?;
+            let writer = decoder.finish()?;
+            let size = head.verify(&writer)?;
 
-        rord_content.push((chain_id, post.clone()));
+            writer.w.sync_all()?;
+            fs::rename(&tmp_path, path)?;
+            fs::remove_file(&part_path)?;
+            size
+        }
+    };
+    fs::remove_file(&state_path)?;
 
-        let payload = chain_client.payload(&post, None)?;
-        let resource_block: Resource = serde_json::from_slice(&payload)?;
+    Ok(Downloaded {
+        bytes: size,
+        reconstructed,
+    })
+}
 
-        match resource_block.prev {
-            None => break,
-            Some(prev) => {
-                let (c, p) = parse_uri(&prev)?;
+/// Fetches resource referenced by a share `uri`, writing it to file at `path`, or on `w` if there
+/// is no path. Returns the number of written bytes, with the blocks rebuilt from parity blocks.
+///
+/// `fchs` URIs are downloaded as [download_file] and [download_resource] do, URIs without chain
+/// being relative to the share `chain`. `file` URIs are copied from the local file system. Other
+/// schemes are refused with an [InputError](seresa::Error::InputError).
+pub fn fetch_uri<T>(
+    mut w: impl Write,
+    path: Option<&Path>,
+    client: &Client<T>,
+    chain: &str,
+    uri: &str,
+    options: &DownloadOptions,
+) -> Result<Downloaded, seresa::Error>
+where
+    T: Connect + Sync,
+{
+    let scheme = uri.split_once(':').map_or("", |(scheme, _)| scheme);
+    match scheme {
+        "fchs" => {
+            let mut uri: FchsUri = uri.parse()?;
+            if uri.chain.is_none() {
+                uri.chain = Some(String::from(chain));
+            }
 
-                if let Some(c) = c {
-                    chain = c;
-                }
-                post = p;
+            match path {
+                Some(path) => download_file(path, client, &uri, false, options),
+                None => download_resource(w, client, &uri, options),
             }
         }
+        "file" => {
+            let source = file_path(uri)?;
+            let size = match path {
+                Some(path) => fs::copy(&source, path)?,
+                None => io::copy(&mut File::open(&source)?, &mut w)?,
+            };
+            Ok(Downloaded {
+                bytes: size as usize,
+                reconstructed: Vec::new(),
+            })
+        }
+        _ => Err(InputError(format!(
+            "unsupported URI scheme of \"{}\", only 'fchs' and 'file' URIs can be fetched",
+            uri
+        ))),
     }
+}
 
-    rord_content.reverse();
-    for (c, p) in rord_content {
-        let chain_client = client.chain(&c);
+/// Mirrors resource ending on post `uri` to the chain of `chain_client`, re-posting each of its
+/// blocks, followed by its manifest, if any, and printing the hash of each post. Returns the URI
+/// of the mirror.
+///
+/// Blocks are fetched as by [download_resource], trusted as set by `options`, and re-posted with
+/// their contents as stored, so compressed and encrypted resources are mirrored as they are,
+/// without their key. Only the `prev` links and the manifest block list are rewritten, to
+/// reference the new posts.
+///
+/// Once posted, the mirror blocks are fetched back and checked to be byte-identical to the
+/// original ones. Mismatches are reported by an [IntegrityError](seresa::Error::IntegrityError)
+/// naming the mirror post.
+pub fn mirror_resource<T>(
+    mut w: impl Write,
+    client: &Client<T>,
+    uri: &FchsUri,
+    chain_client: &ChainClient<T>,
+    signature: Option<&str>,
+    options: &DownloadOptions,
+    output: Output,
+) -> Result<FchsUri, seresa::Error>
+where
+    T: Connect + Sync,
+{
+    let head = open_head(client, uri, options)?;
+    let erasure = head.block.manifest.as_ref().and_then(|m| m.erasure);
 
-        let payload = chain_client.payload(&p, None)?;
-        let resource_block: Resource = serde_json::from_slice(&payload)?;
+    // Hashes of every post, parity blocks included, and of the data blocks, with their digests.
+    let mut posts = Vec::new();
+    let mut blocks: Vec<(String, Vec<u8>)> = Vec::new();
+    // Stored data blocks of the current stripe, with the last one, the parity blocks template.
+    let mut stripe = Vec::new();
+    let mut last = None;
+    fetch_posts(client, &head, options, 0, |chain, post, block| {
+        let stored = decode_content(chain, post, &block)?;
+        let digest = Sha256::digest(&stored).to_vec();
 
-        let content = base64::decode(resource_block.content)
-            .or(Err(InvalidContentError(c.to_string(), p.clone())))?;
+        let resource = Resource {
+            prev: blocks.last().map(|(h, _)| FchsUri::relative(h).to_string()),
+            ..block
+        };
+        let payload = serde_json::to_vec(&resource)?;
+        if payload.len() > MAX_PAYLOAD_SIZE {
+            return Err(InputError(format!(
+                "block \"{}\" does not fit on a post of chain \"{}\", its resource must be \
+                uploaded again to be mirrored",
+                post,
+                chain_client.name()
+            )));
+        }
 
-        w.write_all(&content)?;
-    }
+        let hash = chain_client.post(signature, false, &payload)?;
+        write_post(&mut w, output, None, "block", blocks.len(), &hash)?;
+        posts.push(hash.clone());
 
-    Ok(())
-}
+        if let Some(erasure) = erasure {
+            let shard = Shard::of(&stored, &resource);
+            stripe.push((stored, shard));
+            let template = Resource {
+                prev: Some(FchsUri::relative(&hash).to_string()),
+                ..resource
+            };
+            if stripe.len() == erasure.data {
+                for hash in
+                    post_parity(chain_client, &template, &stripe, erasure.parity, signature)?
+                {
+                    write_post(
+                        &mut w,
+                        output,
+                        None,
+                        "parity",
+                        posts.len() - blocks.len() - 1,
+                        &hash,
+                    )?;
+                    posts.push(hash);
+                }
+                stripe.clear();
+            }
+            last = Some(template);
+        }
 
-fn parse_uri(uri: &str) -> Result<(Option<String>, String), seresa::Error> {
-    let (scheme, uri) = uri
-        .split_once(':')
-        .ok_or(InputError(String::from("no scheme specified on URI")))?;
-    if scheme != "fchs" {
-        return Err(InputError(String::from("invalid URI scheme")));
+        blocks.push((hash, digest));
+        Ok(())
+    })?;
+    if let (Some(erasure), Some(template)) = (erasure, &last) {
+        if !stripe.is_empty() {
+            for hash in post_parity(chain_client, template, &stripe, erasure.parity, signature)? {
+                write_post(
+                    &mut w,
+                    output,
+                    None,
+                    "parity",
+                    posts.len() - blocks.len(),
+                    &hash,
+                )?;
+                posts.push(hash);
+            }
+        }
     }
 
-    match uri.rsplit_once(':') {
-        None => Ok((None, String::from(uri))),
-        Some((uri, post)) => {
-            if uri.len() == 0 {
-                return Err(InputError(String::from(
-                    "invalid 'fchs' URI format, missing chain name",
-                )));
-            }
-            let chain = match uri.split_once(':') {
-                None => uri,
-                Some((chain, _)) => chain,
+    let post = match &head.block.manifest {
+        Some(manifest) => {
+            let resource = Resource {
+                prev: blocks.last().map(|(h, _)| FchsUri::relative(h).to_string()),
+                manifest: Some(Manifest {
+                    blocks: posts,
+                    indexes: Vec::new(),
+                    ..manifest.clone()
+                }),
+                ..head.block.clone()
             };
 
-            Ok((Some(String::from(chain)), String::from(post)))
+            let hash = post_manifest(&mut w, chain_client, resource, signature, output)?;
+            output.write_line(&mut w, &hash, &json!({ "manifest": hash }))?;
+            hash
+        }
+        None => blocks
+            .last()
+            .map(|(h, _)| h.clone())
+            .expect("resource without manifest has at least its last block"),
+    };
+    let mirror = FchsUri::new(chain_client.name(), &post);
+
+    // The mirror is only compared to the original, which was already trusted.
+    let options = DownloadOptions {
+        jobs: options.jobs,
+        min_reps: None,
+        consensus: false,
+        key: None,
+    };
+    let head = open_head(client, &mirror, &options)?;
+
+    let mut i = 0;
+    fetch_posts(client, &head, &options, 0, |chain, post, block| {
+        let digest = Sha256::digest(decode_content(chain, post, &block)?).to_vec();
+        if blocks.get(i).map(|(_, d)| d) != Some(&digest) {
+            return Err(IntegrityError(String::from(chain), String::from(post)));
         }
+
+        i += 1;
+        Ok(())
+    })?;
+    if i != blocks.len() {
+        return Err(IntegrityError(head.chain, head.post));
+    }
+
+    Ok(mirror)
+}
+
+/// Progress of a download to a file, stored next to the partial file.
+#[derive(Debug, Deserialize, Serialize)]
+struct PartialDownload {
+    /// Downloaded resource URI.
+    uri: String,
+
+    /// Blocks written to the partial file, in order.
+    blocks: Vec<PartialBlock>,
+}
+
+/// Resource block written to a partial file.
+#[derive(Debug, Deserialize, Serialize)]
+struct PartialBlock {
+    /// Block post hash.
+    post: String,
+
+    /// Block content length, in bytes.
+    size: usize,
+
+    /// Hex encoded SHA-256 digest of the block content.
+    sha256: String,
+}
+
+/// Post referenced by a resource URI, either its manifest or its last block.
+struct Head {
+    chain: String,
+    post: String,
+    block: Resource,
+
+    /// Cipher of the blocks, [None] if the resource is not encrypted.
+    cipher: Option<BlockCipher>,
+
+    /// Trust policy of the head chain.
+    trust: Trust,
+}
+
+impl Head {
+    /// Returns resource compression, [None] if not compressed or if there is no manifest.
+    fn compression(&self) -> Option<Compression> {
+        self.block.manifest.as_ref().and_then(|m| m.compression)
+    }
+
+    /// Verifies resource contents, hashed by `writer`, against the resource manifest, if any.
+    /// Returns the resource length.
+    fn verify<W>(&self, writer: &HashWriter<W>) -> Result<usize, seresa::Error> {
+        if let Some(manifest) = &self.block.manifest {
+            let digest = format!("{:x}", writer.hasher.clone().finalize());
+            if writer.size != manifest.size || digest != manifest.sha256 {
+                return Err(IntegrityError(self.chain.clone(), self.post.clone()));
+            }
+        }
+
+        Ok(writer.size as usize)
+    }
+}
+
+/// Checks deciding whether the blocks of a chain are trusted.
+struct Trust {
+    /// Minimum reputation of each block, [None] if reputation is ignored.
+    min_reps: Option<isize>,
+
+    /// Posts on the chain consensus, [None] if consensus is not checked.
+    consensus: Option<HashSet<String>>,
+}
+
+impl Trust {
+    /// Builds trust policy of the chain of `chain_client`, fetching its consensus if
+    /// `options.consensus` is set.
+    fn new<T>(
+        chain_client: &ChainClient<T>,
+        options: &DownloadOptions,
+    ) -> Result<Trust, seresa::Error>
+    where
+        T: Connect,
+    {
+        let consensus = if options.consensus {
+            Some(chain_client.consensus()?.into_iter().collect())
+        } else {
+            None
+        };
+
+        Ok(Trust {
+            min_reps: options.min_reps,
+            consensus,
+        })
+    }
+}
+
+/// Fetches the post referenced by resource `uri`, opening the resource cipher with `options.key`
+/// if it is encrypted.
+fn open_resource<T>(
+    client: &Client<T>,
+    uri: &FchsUri,
+    options: &DownloadOptions,
+) -> Result<Head, seresa::Error>
+where
+    T: Connect,
+{
+    let mut head = open_head(client, uri, options)?;
+
+    head.cipher = match (&head.block.encryption, &options.key) {
+        (None, _) => None,
+        (Some(encryption), Some(key)) => Some(BlockCipher::open(encryption, key)?),
+        (Some(_), None) => {
+            return Err(InputError(String::from(
+                "resource is encrypted, a passphrase or a private key is needed to download it",
+            )))
+        }
+    };
+
+    Ok(head)
+}
+
+/// Fetches the post referenced by resource `uri`, without opening the resource cipher.
+fn open_head<T>(
+    client: &Client<T>,
+    uri: &FchsUri,
+    options: &DownloadOptions,
+) -> Result<Head, seresa::Error>
+where
+    T: Connect,
+{
+    let chain = uri
+        .chain
+        .clone()
+        .ok_or(InputError(String::from("missing chains name on URI")))?;
+    let post = uri.post.clone();
+
+    let chain_id = find_chain(client, &chain, &uri.keys)?;
+    let chain_client = client.chain(&chain_id);
+    let trust = Trust::new(&chain_client, options)?;
+    let block = fetch_block(&chain_client, &post, &trust)?;
+
+    Ok(Head {
+        chain,
+        post,
+        block,
+        cipher: None,
+        trust,
+    })
+}
+
+/// Fetches blocks of resource `head`, passing the content of each block to `write`, in order,
+/// with the block post hash. The first `skip` blocks, already downloaded, are left out. Returns
+/// hashes of the blocks rebuilt from parity blocks, in order.
+fn fetch_resource<T>(
+    client: &Client<T>,
+    head: &Head,
+    options: &DownloadOptions,
+    skip: usize,
+    mut write: impl FnMut(&str, &[u8]) -> Result<(), seresa::Error>,
+) -> Result<Vec<String>, seresa::Error>
+where
+    T: Connect + Sync,
+{
+    fetch_posts(client, head, options, skip, |chain, post, block| {
+        let content = decode_block(chain, post, block, head.cipher.as_ref())?;
+        write(post, &content)
+    })
+}
+
+/// Fetches block posts of resource `head`, passing each block to `f`, in order, with its chain and
+/// post hash. The first `skip` blocks are left out. Returns hashes of the blocks rebuilt from
+/// parity blocks, in order.
+fn fetch_posts<T>(
+    client: &Client<T>,
+    head: &Head,
+    options: &DownloadOptions,
+    skip: usize,
+    mut f: impl FnMut(&str, &str, Resource) -> Result<(), seresa::Error>,
+) -> Result<Vec<String>, seresa::Error>
+where
+    T: Connect + Sync,
+{
+    if let Some(manifest) = &head.block.manifest {
+        let chain_id = find_chain(client, &head.chain, &[])?;
+        let chain_client = client.chain(&chain_id);
+        let blocks = list_blocks(&chain_client, head, manifest)?;
+        let stripes = split_stripes(&head.chain, &head.post, blocks, manifest.erasure)?;
+        let data: Vec<String> = stripes.iter().flat_map(|s| s.data.clone()).collect();
+
+        let skip = skip.min(data.len());
+        let mut alternates = Alternates {
+            client,
+            manifest,
+            options,
+            copies: None,
+        };
+        let mut parity = Parity {
+            chain_client: &chain_client,
+            head,
+            stripes: &stripes,
+            rebuilt: None,
+            reconstructed: Vec::new(),
+        };
+        fetch_blocks(
+            &chain_client,
+            &data[skip..],
+            &head.trust,
+            options.jobs,
+            |i, error| {
+                alternates
+                    .fetch(skip + i, error)
+                    .or_else(|error| parity.rebuild(skip + i, error))
+            },
+            f,
+        )?;
+        return Ok(parity.reconstructed);
+    }
+
+    // Blocks may be on other chains, each with its own consensus.
+    let mut trusts = HashMap::new();
+    let mut rord_content = vec![(head.chain.clone(), head.post.clone(), head.block.clone())];
+    while let Some(prev) = rord_content.last().and_then(|(_, _, b)| b.prev.clone()) {
+        let prev: FchsUri = prev.parse()?;
+        let c = prev
+            .chain
+            .unwrap_or_else(|| rord_content.last().unwrap().0.clone());
+        let p = prev.post;
+
+        let chain_id = find_chain(client, &c, &prev.keys)?;
+        let chain_client = client.chain(&chain_id);
+        let trust = if c == head.chain {
+            &head.trust
+        } else {
+            match trusts.entry(c.clone()) {
+                Entry::Occupied(e) => e.into_mut(),
+                Entry::Vacant(e) => e.insert(Trust::new(&chain_client, options)?),
+            }
+        };
+        let block = fetch_block(&chain_client, &p, trust)?;
+        rord_content.push((c, p, block));
+    }
+
+    for (c, p, block) in rord_content.into_iter().rev().skip(skip) {
+        f(&c, &p, block)?;
+    }
+
+    Ok(Vec::new())
+}
+
+/// Returns local path of `file` URI, such as `file:///home/user/paper.pdf`.
+fn file_path(uri: &str) -> Result<PathBuf, seresa::Error> {
+    let path = uri.strip_prefix("file:").unwrap_or(uri);
+    // Authority must be empty or the local host.
+    let path = match path.strip_prefix("//") {
+        Some(rest) => match rest.find('/') {
+            Some(i) if matches!(&rest[..i], "" | "localhost") => &rest[i..],
+            _ => {
+                return Err(InputError(format!(
+                    "'file' URI \"{}\" is not on the local host",
+                    uri
+                )))
+            }
+        },
+        None => path,
+    };
+    if !path.starts_with('/') {
+        return Err(InputError(format!(
+            "'file' URI \"{}\" must have an absolute path",
+            uri
+        )));
+    }
+
+    percent_decode(path)
+        .map(PathBuf::from)
+        .ok_or_else(|| InputError(format!("invalid 'file' URI \"{}\"", uri)))
+}
+
+/// Decodes percent encoded bytes of `text`, [None] if they are not valid UTF-8.
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = match bytes[i] {
+            b'%' => {
+                let hex = text.get(i + 1..i + 3)?;
+                i += 2;
+                u8::from_str_radix(hex, 16).ok()?
+            }
+            b => b,
+        };
+        decoded.push(byte);
+        i += 1;
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+/// Returns hashes of the blocks listed by `manifest` of resource `head`, in order, fetching its
+/// index posts, if any.
+fn list_blocks<T>(
+    chain_client: &ChainClient<T>,
+    head: &Head,
+    manifest: &Manifest,
+) -> Result<Vec<String>, seresa::Error>
+where
+    T: Connect,
+{
+    if manifest.indexes.is_empty() {
+        return Ok(manifest.blocks.clone());
+    }
+
+    let mut blocks = Vec::new();
+    for post in &manifest.indexes {
+        let index = fetch_block(chain_client, post, &head.trust)?
+            .index
+            .ok_or_else(|| InvalidContentError(head.chain.clone(), post.clone()))?;
+        blocks.extend(index);
+    }
+
+    Ok(blocks)
+}
+
+/// Returns `path` with `extension` appended to its file name, such as `paper.pdf.part`.
+fn append_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(extension);
+    path.with_file_name(name)
+}
+
+/// Parses `chain` name, checking if the chain is on the Freechains node. Chains not on the node
+/// are joined with the forum `keys` of their URI, if any.
+fn find_chain<T>(client: &Client<T>, chain: &str, keys: &[String]) -> Result<ChainId, seresa::Error>
+where
+    T: Connect,
+{
+    let chain_id = ChainId::new(chain).map_err(|c| {
+        InputError(format!(
+            "invalid 'fchs' URI format, invalid chain name \"{}\"",
+            c
+        ))
+    })?;
+
+    if !client.chains()?.contains(&chain_id) {
+        if keys.is_empty() {
+            return Err(InputError(format!(
+                "invalid 'fchs' URI, chain \"{}\" not on Freechains node",
+                chain_id
+            )));
+        }
+
+        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+        client.join_chain(&chain_id, &keys)?;
+    }
+
+    Ok(chain_id)
+}
+
+/// Fetches resource block `post`, checking it is trusted by `trust`.
+fn fetch_block<T>(
+    chain_client: &ChainClient<T>,
+    post: &str,
+    trust: &Trust,
+) -> Result<Resource, seresa::Error>
+where
+    T: Connect,
+{
+    if let Some(min_reps) = trust.min_reps {
+        let reps = chain_client.reputation(post)?;
+        if reps < min_reps {
+            return Err(LowReputationError(
+                String::from(chain_client.name()),
+                String::from(post),
+            ));
+        }
+    }
+
+    if trust.consensus.as_ref().is_some_and(|c| !c.contains(post)) {
+        return Err(ConsensusError(
+            String::from(chain_client.name()),
+            String::from(post),
+        ));
+    }
+
+    let payload = chain_client.payload(post, None)?;
+    let block = serde_json::from_slice(&payload)?;
+    Ok(block)
+}
+
+/// Fetches resource blocks `posts` trusted by `trust` using `jobs` threads, passing each block to
+/// `f`, in order, with its chain and post hash. Blocks which cannot be fetched are passed, by their
+/// position, with the fetch error, to `fallback`, which returns the block from elsewhere.
+///
+/// Fetching stops on the first error, either from `fallback` or from `f`.
+fn fetch_blocks<T>(
+    chain_client: &ChainClient<T>,
+    posts: &[String],
+    trust: &Trust,
+    jobs: usize,
+    mut fallback: impl FnMut(usize, seresa::Error) -> Result<(String, String, Resource), seresa::Error>,
+    mut f: impl FnMut(&str, &str, Resource) -> Result<(), seresa::Error>,
+) -> Result<(), seresa::Error>
+where
+    T: Connect + Sync,
+{
+    let jobs = jobs.clamp(1, posts.len().max(1));
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+
+    // Position of the next block to be written, [None] once writing stopped. Blocks are only
+    // fetched a few positions ahead of it, so a slow block does not make every later block wait
+    // in memory.
+    let written = Mutex::new(Some(0));
+    let advanced = Condvar::new();
+    let ahead = 2 * jobs;
+
+    thread::scope(|s| {
+        for _ in 0..jobs {
+            let tx = tx.clone();
+            let (next, written, advanced) = (&next, &written, &advanced);
+            s.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= posts.len() {
+                    break;
+                }
+
+                let position = advanced
+                    .wait_while(
+                        written.lock().unwrap_or_else(PoisonError::into_inner),
+                        |w| w.is_some_and(|w| i >= w + ahead),
+                    )
+                    .unwrap_or_else(PoisonError::into_inner);
+                if position.is_none() {
+                    break;
+                }
+                drop(position);
+
+                // Receiver is gone if writing failed.
+                if tx
+                    .send((i, fetch_block(chain_client, &posts[i], trust)))
+                    .is_err()
+                {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        // Blocks may arrive out of order, and wait here until the blocks before them are written.
+        let mut pending = BTreeMap::new();
+        let mut i = 0;
+        let result = rx.iter().try_for_each(|(j, block)| {
+            pending.insert(j, block);
+            while let Some(block) = pending.remove(&i) {
+                match block {
+                    Ok(block) => f(chain_client.name(), &posts[i], block)?,
+                    Err(error) => {
+                        let (chain, post, block) = fallback(i, error)?;
+                        f(&chain, &post, block)?;
+                    }
+                }
+                i += 1;
+
+                *written.lock().unwrap_or_else(PoisonError::into_inner) = Some(i);
+                advanced.notify_all();
+            }
+            Ok(())
+        });
+
+        // Workers waiting for their turn must stop if writing failed.
+        *written.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        advanced.notify_all();
+        result
+    })
+}
+
+/// Copies of a resource on other chains, listed as alternates on its `manifest`, opened when the
+/// first block of the resource cannot be fetched.
+struct Alternates<'a, T> {
+    client: &'a Client<T>,
+    manifest: &'a Manifest,
+    options: &'a DownloadOptions<'a>,
+
+    /// Opened copies, [None] if not opened yet. Copies which cannot be opened, or are of another
+    /// resource, are left out.
+    copies: Option<Vec<Replica<'a, T>>>,
+}
+
+/// Copy of a resource on a chain.
+struct Replica<'a, T> {
+    chain_client: ChainClient<'a, T>,
+
+    /// Trust policy of the copy chain.
+    trust: Trust,
+
+    /// Hashes of the copy data blocks, in order.
+    blocks: Vec<String>,
+}
+
+impl<'a, T> Alternates<'a, T>
+where
+    T: Connect,
+{
+    /// Fetches block `i` from the first copy which has it trusted, once fetching it from the
+    /// resource chain failed with `error`. Returns the block with its chain and post hash, or
+    /// `error` if no copy has it, or if the block is not missing nor untrusted.
+    fn fetch(
+        &mut self,
+        i: usize,
+        error: seresa::Error,
+    ) -> Result<(String, String, Resource), seresa::Error> {
+        if !is_unavailable(&error) {
+            return Err(error);
+        }
+
+        if self.copies.is_none() {
+            self.copies = Some(self.open());
+        }
+        for copy in self.copies.iter().flatten() {
+            if let Some(post) = copy.blocks.get(i) {
+                if let Ok(block) = fetch_block(&copy.chain_client, post, &copy.trust) {
+                    return Ok((String::from(copy.chain_client.name()), post.clone(), block));
+                }
+            }
+        }
+
+        Err(error)
+    }
+
+    /// Opens the copies listed as alternates, in order.
+    fn open(&self) -> Vec<Replica<'a, T>> {
+        self.manifest
+            .alternates
+            .iter()
+            .filter_map(|uri| {
+                let head = open_head(self.client, &uri.parse().ok()?, self.options).ok()?;
+                let manifest = head.block.manifest.as_ref()?;
+                if manifest.size != self.manifest.size || manifest.sha256 != self.manifest.sha256 {
+                    return None;
+                }
+
+                let chain_id = find_chain(self.client, &head.chain, &[]).ok()?;
+                let chain_client = self.client.chain(&chain_id);
+                let blocks = list_blocks(&chain_client, &head, manifest).ok()?;
+                let blocks = split_stripes(&head.chain, &head.post, blocks, manifest.erasure)
+                    .ok()?
+                    .into_iter()
+                    .flat_map(|s| s.data)
+                    .collect();
+                Some(Replica {
+                    chain_client,
+                    trust: head.trust,
+                    blocks,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Stripe of resource blocks, whose data blocks are followed by their parity blocks.
+struct Stripe {
+    data: Vec<String>,
+    parity: Vec<String>,
+}
+
+/// Splits `blocks` of resource manifest `post` on `chain` in stripes. Resources without `erasure`
+/// coding have a single stripe, without parity blocks.
+fn split_stripes(
+    chain: &str,
+    post: &str,
+    blocks: Vec<String>,
+    erasure: Option<Erasure>,
+) -> Result<Vec<Stripe>, seresa::Error> {
+    let erasure = match erasure {
+        Some(erasure) => erasure,
+        None => {
+            return Ok(vec![Stripe {
+                data: blocks,
+                parity: Vec::new(),
+            }])
+        }
+    };
+
+    let invalid = || InvalidContentError(String::from(chain), String::from(post));
+    if erasure.data == 0
+        || erasure.parity == 0
+        || erasure.data + erasure.parity > Erasure::MAX_BLOCKS
+    {
+        return Err(invalid());
+    }
+
+    blocks
+        .chunks(erasure.data + erasure.parity)
+        .map(|stripe| {
+            let data_count = stripe.len().checked_sub(erasure.parity).filter(|&n| n > 0);
+            let (data, parity) = stripe.split_at(data_count.ok_or_else(invalid)?);
+            Ok(Stripe {
+                data: data.to_vec(),
+                parity: parity.to_vec(),
+            })
+        })
+        .collect()
+}
+
+/// Parity blocks of an erasure coded resource, used to rebuild the data blocks which cannot be
+/// fetched.
+struct Parity<'a, T> {
+    chain_client: &'a ChainClient<'a, T>,
+    head: &'a Head,
+    stripes: &'a [Stripe],
+
+    /// Position of the last rebuilt stripe, with its data blocks.
+    rebuilt: Option<(usize, Vec<Resource>)>,
+
+    /// Hashes of the rebuilt blocks, in order.
+    reconstructed: Vec<String>,
+}
+
+impl<T> Parity<'_, T>
+where
+    T: Connect,
+{
+    /// Rebuilds data block `i` from the other blocks of its stripe, once fetching it failed with
+    /// `error`. Returns the block with its chain and post hash, or `error` if too few blocks of
+    /// the stripe are trusted, or if the block is not missing nor untrusted.
+    fn rebuild(
+        &mut self,
+        i: usize,
+        error: seresa::Error,
+    ) -> Result<(String, String, Resource), seresa::Error> {
+        if !is_unavailable(&error) {
+            return Err(error);
+        }
+
+        let (mut s, mut j) = (0, i);
+        while s < self.stripes.len() && j >= self.stripes[s].data.len() {
+            j -= self.stripes[s].data.len();
+            s += 1;
+        }
+        let stripe = match self.stripes.get(s) {
+            Some(stripe) if !stripe.parity.is_empty() => stripe,
+            _ => return Err(error),
+        };
+
+        if self.rebuilt.as_ref().map(|(r, _)| *r) != Some(s) {
+            match self.rebuild_stripe(stripe) {
+                Some(blocks) => self.rebuilt = Some((s, blocks)),
+                None => return Err(error),
+            }
+        }
+        let (_, blocks) = self.rebuilt.as_ref().expect("stripe was just rebuilt");
+
+        let post = stripe.data[j].clone();
+        self.reconstructed.push(post.clone());
+        Ok((self.head.chain.clone(), post, blocks[j].clone()))
+    }
+
+    /// Rebuilds the data blocks of `stripe` from its trusted blocks, [None] if too few are left.
+    fn rebuild_stripe(&self, stripe: &Stripe) -> Option<Vec<Resource>> {
+        let fetch = |post: &String| {
+            let block = fetch_block(self.chain_client, post, &self.head.trust).ok()?;
+            let content = decode_content(&self.head.chain, post, &block).ok()?;
+            Some((block, content))
+        };
+
+        let parity: Vec<Option<(Resource, Vec<u8>)>> = stripe.parity.iter().map(fetch).collect();
+        let (template, content) = parity.iter().flatten().next()?;
+        let description = template
+            .stripe
+            .clone()
+            .filter(|d| d.len() == stripe.data.len())?;
+        let len = content.len();
+
+        // Shards must have the same length, so shorter data blocks are padded with zeros.
+        let mut shards: Vec<Option<Vec<u8>>> = stripe
+            .data
+            .iter()
+            .map(|post| {
+                let (_, mut content) = fetch(post).filter(|(_, c)| c.len() <= len)?;
+                content.resize(len, 0);
+                Some(content)
+            })
+            .chain(parity.iter().map(|p| {
+                p.as_ref()
+                    .map(|(_, c)| c.clone())
+                    .filter(|c| c.len() == len)
+            }))
+            .collect();
+        ReedSolomon::new(stripe.data.len(), stripe.parity.len())
+            .ok()?
+            .reconstruct_data(&mut shards)
+            .ok()?;
+
+        let encoding = template.encoding.unwrap_or(Encoding::Base64);
+        shards
+            .into_iter()
+            .zip(description)
+            .map(|(shard, shard_description)| {
+                let mut content = shard?;
+                if shard_description.size > content.len() {
+                    return None;
+                }
+                content.truncate(shard_description.size);
+                if format!("{:x}", Sha256::digest(&content)) != shard_description.sha256 {
+                    return None;
+                }
+
+                Some(Resource {
+                    title: template.title.clone(),
+                    content: encoding.encode(&content),
+                    encoding: Some(encoding),
+                    prev: None,
+                    sha256: Some(shard_description.sha256),
+                    encryption: template.encryption.clone(),
+                    nonce: shard_description.nonce,
+                    index: None,
+                    stripe: None,
+                    manifest: None,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Returns whether `error` reports a block missing or not trusted, which may be found elsewhere.
+fn is_unavailable(error: &seresa::Error) -> bool {
+    matches!(
+        error,
+        FreechainsError(_) | LowReputationError(..) | ConsensusError(..)
+    )
+}
+
+/// Decodes resource block `post` content, verifying its digest, and decrypts it with `cipher` if
+/// the block is encrypted.
+fn decode_block(
+    chain: &str,
+    post: &str,
+    block: Resource,
+    cipher: Option<&BlockCipher>,
+) -> Result<Vec<u8>, seresa::Error> {
+    let content = decode_content(chain, post, &block)?;
+
+    let encryption = match &block.encryption {
+        Some(encryption) => encryption,
+        None => return Ok(content),
+    };
+    // Blocks of other resources may be linked, but never decrypted with this resource key.
+    let cipher = cipher
+        .filter(|c| c.encryption() == encryption)
+        .ok_or_else(|| DecryptionError(String::from(chain), String::from(post)))?;
+    let nonce = block
+        .nonce
+        .as_deref()
+        .ok_or_else(|| InvalidContentError(String::from(chain), String::from(post)))?;
+
+    cipher
+        .decrypt(nonce, &content)
+        .ok_or_else(|| DecryptionError(String::from(chain), String::from(post)))
+}
+
+/// Decodes resource block `post` content, as stored, verifying its digest.
+fn decode_content(chain: &str, post: &str, block: &Resource) -> Result<Vec<u8>, seresa::Error> {
+    let encoding = block.encoding.unwrap_or(Encoding::Base64);
+    let content = encoding
+        .decode(&block.content)
+        .ok_or_else(|| InvalidContentError(String::from(chain), String::from(post)))?;
+
+    if let Some(digest) = &block.sha256 {
+        if format!("{:x}", Sha256::digest(&content)) != digest.to_lowercase() {
+            return Err(IntegrityError(String::from(chain), String::from(post)));
+        }
+    }
+
+    Ok(content)
+}
+
+impl Encoding {
+    /// Names accepted by [FromStr].
+    pub const NAMES: &'static [&'static str] = &["base64", "z85"];
+
+    /// Returns encoding name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Base64 => "base64",
+            Encoding::Z85 => "z85",
+        }
+    }
+
+    /// Encodes `content`.
+    fn encode(self, content: &[u8]) -> String {
+        match self {
+            Encoding::Base64 => base64::encode(content),
+            Encoding::Z85 => z85::encode(content),
+        }
+    }
+
+    /// Decodes `text`, [None] if it is not valid on the encoding.
+    fn decode(self, text: &str) -> Option<Vec<u8>> {
+        match self {
+            Encoding::Base64 => base64::decode(text).ok(),
+            Encoding::Z85 => z85::decode(text).ok(),
+        }
+    }
+
+    /// Returns the largest number of bytes whose encoding fits on `len` characters.
+    fn capacity(self, len: usize) -> usize {
+        match self {
+            Encoding::Base64 => len / 4 * 3,
+            Encoding::Z85 => len / 5 * 4,
+        }
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = seresa::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "base64" => Ok(Encoding::Base64),
+            "z85" => Ok(Encoding::Z85),
+            _ => Err(InputError(format!("unknown encoding \"{}\"", s))),
+        }
+    }
+}
+
+/// Returns how many bytes of content fit on block `resource`, which has no content yet, so the
+/// block post is not larger than [MAX_PAYLOAD_SIZE]. Content grows by `tag_size` bytes when it is
+/// encrypted.
+///
+/// Room is left for the widest `prev` link, so the block still fits when it is mirrored to a chain
+/// with taller posts.
+fn block_capacity(
+    resource: &Resource,
+    encoding: Encoding,
+    tag_size: usize,
+) -> Result<usize, seresa::Error> {
+    let resource = Resource {
+        prev: Some("0".repeat(MAX_PREV_LEN)),
+        ..resource.clone()
+    };
+    let overhead = serde_json::to_vec(&resource)?.len();
+    match encoding
+        .capacity(MAX_PAYLOAD_SIZE.saturating_sub(overhead))
+        .saturating_sub(tag_size)
+    {
+        0 => Err(InputError(String::from(
+            "resource title is too long to fit on a block post",
+        ))),
+        capacity => Ok(capacity),
+    }
+}
+
+impl Erasure {
+    /// Largest number of blocks of a stripe.
+    pub const MAX_BLOCKS: usize = 256;
+}
+
+/// Parses `<data blocks>:<total blocks>`, such as `4:6`, where any 4 of each 6 blocks recover
+/// the stripe.
+impl FromStr for Erasure {
+    type Err = seresa::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            InputError(format!(
+                "invalid erasure coding \"{}\", expected <data blocks>:<total blocks>, \
+                such as 4:6",
+                s
+            ))
+        };
+
+        let (data, total) = s.split_once(':').ok_or_else(invalid)?;
+        let data: usize = data.parse().map_err(|_| invalid())?;
+        let total: usize = total.parse().map_err(|_| invalid())?;
+        if data == 0 || total <= data || total > Erasure::MAX_BLOCKS {
+            return Err(invalid());
+        }
+
+        Ok(Erasure {
+            data,
+            parity: total - data,
+        })
+    }
+}
+
+impl fmt::Display for Erasure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.data, self.data + self.parity)
+    }
+}
+
+impl Compression {
+    /// Returns codec name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Compression::Deflate => "deflate",
+        }
+    }
+}
+
+/// Reader hashing everything read from the inner reader.
+struct HashReader<R> {
+    r: R,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<R> HashReader<R> {
+    fn new(r: R) -> HashReader<R> {
+        HashReader {
+            r,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    /// Returns length and hex encoded SHA-256 digest of everything read.
+    fn finish(&self) -> (u64, String) {
+        (self.size, format!("{:x}", self.hasher.clone().finalize()))
+    }
+}
+
+impl<R: Read> Read for HashReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.r.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.size += n as u64;
+        Ok(n)
+    }
+}
+
+/// Writer hashing everything written to the inner writer.
+struct HashWriter<W> {
+    w: W,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<W> HashWriter<W> {
+    fn new(w: W) -> HashWriter<W> {
+        HashWriter {
+            w,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for HashWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.w.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+/// Reader of a resource, compressing it before it is cut in blocks.
+enum Encoder<R: Read> {
+    Plain(R),
+    Deflate(DeflateEncoder<R>),
+}
+
+fn encoder<R: Read>(r: R, compression: Option<Compression>) -> Encoder<R> {
+    match compression {
+        None => Encoder::Plain(r),
+        Some(Compression::Deflate) => {
+            Encoder::Deflate(DeflateEncoder::new(r, flate2::Compression::best()))
+        }
+    }
+}
+
+impl<R: Read> Encoder<R> {
+    fn get_ref(&self) -> &R {
+        match self {
+            Encoder::Plain(r) => r,
+            Encoder::Deflate(r) => r.get_ref(),
+        }
+    }
+}
+
+impl<R: Read> Read for Encoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Plain(r) => r.read(buf),
+            Encoder::Deflate(r) => r.read(buf),
+        }
+    }
+}
+
+/// Writer of a resource, decompressing the contents of its blocks.
+enum Decoder<W: Write> {
+    Plain(W),
+    Deflate(DeflateDecoder<W>),
+}
+
+impl<W: Write> Decoder<W> {
+    fn new(w: W, compression: Option<Compression>) -> Decoder<W> {
+        match compression {
+            None => Decoder::Plain(w),
+            Some(Compression::Deflate) => Decoder::Deflate(DeflateDecoder::new(w)),
+        }
+    }
+
+    /// Writes any buffered contents, returning the inner writer.
+    fn finish(self) -> io::Result<W> {
+        match self {
+            Decoder::Plain(w) => Ok(w),
+            Decoder::Deflate(w) => w.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Decoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Decoder::Plain(w) => w.write(buf),
+            Decoder::Deflate(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Decoder::Plain(w) => w.flush(),
+            Decoder::Deflate(w) => w.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashes(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("{}_{:064X}", i + 1, i)).collect()
+    }
+
+    fn erasure(data: usize, parity: usize) -> Option<Erasure> {
+        Some(Erasure { data, parity })
+    }
+
+    fn split(blocks: &[String], erasure: Option<Erasure>) -> Result<Vec<Stripe>, seresa::Error> {
+        split_stripes("#forum", "1_MANIFEST", blocks.to_vec(), erasure)
+    }
+
+    #[test]
+    fn parses_erasure() {
+        let erasure: Erasure = "4:6".parse().unwrap();
+        assert_eq!(erasure, Erasure { data: 4, parity: 2 });
+        assert_eq!(erasure.to_string(), "4:6");
+
+        assert_eq!("1:2".parse::<Erasure>().unwrap().parity, 1);
+        assert_eq!("255:256".parse::<Erasure>().unwrap().data, 255);
+    }
+
+    #[test]
+    fn rejects_invalid_erasure() {
+        for s in &[
+            "", "4", "4:", ":6", "4:6:8", "a:6", "4:b", "-1:2", "0:2", "4:4", "6:4", "200:300",
+        ] {
+            assert!(s.parse::<Erasure>().is_err(), "{:?} was parsed", s);
+        }
+    }
+
+    #[test]
+    fn splits_blocks_without_erasure_in_a_single_stripe() {
+        let blocks = hashes(5);
+        let stripes = split(&blocks, None).unwrap();
+
+        assert_eq!(stripes.len(), 1);
+        assert_eq!(stripes[0].data, blocks);
+        assert!(stripes[0].parity.is_empty());
+    }
+
+    #[test]
+    fn splits_blocks_in_stripes() {
+        // Two full 2:3 stripes, followed by a last stripe of a single data block.
+        let blocks = hashes(8);
+        let stripes = split(&blocks, erasure(2, 1)).unwrap();
+
+        let layout: Vec<_> = stripes
+            .iter()
+            .map(|s| (s.data.clone(), s.parity.clone()))
+            .collect();
+        assert_eq!(
+            layout,
+            vec![
+                (blocks[0..2].to_vec(), blocks[2..3].to_vec()),
+                (blocks[3..5].to_vec(), blocks[5..6].to_vec()),
+                (blocks[6..7].to_vec(), blocks[7..8].to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_stripes_without_data_blocks() {
+        // Last stripe only has parity blocks.
+        assert!(split(&hashes(8), erasure(4, 2)).is_err());
+        assert!(split(&hashes(1), erasure(1, 1)).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_manifest_erasure() {
+        assert!(split(&hashes(4), erasure(0, 2)).is_err());
+        assert!(split(&hashes(4), erasure(2, 0)).is_err());
+        assert!(split(&hashes(4), erasure(200, 100)).is_err());
     }
 }
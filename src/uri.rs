@@ -0,0 +1,196 @@
+//! Freechains `fchs` URIs, identifying a post on a public, private or public identity forum.
+//!
+//! URIs have the form `fchs:[<forum>[:<forum keys>]]:<post hash>`, where:
+//!
+//! - `forum` is the name of the forum, such as `#forum`, `$private` or `@<public key>`. URIs
+//!   without forum are relative to the forum of the post they are found on.
+//! - `forum keys` is a list of hex encoded keys separated by colons, used to join the forum.
+//! - `post hash` is the hash of the post, such as `2_DF29...9EA4`.
+
+use crate::Error;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Parsed `fchs` URI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FchsUri {
+    /// Forum name, [None] if the URI is relative to the forum it is found on.
+    pub chain: Option<String>,
+
+    /// Keys used to join the forum, in order.
+    pub keys: Vec<String>,
+
+    /// Post hash.
+    pub post: String,
+}
+
+impl FchsUri {
+    /// URI of `post` on `chain`, without keys.
+    pub fn new(chain: &str, post: &str) -> FchsUri {
+        FchsUri {
+            chain: Some(String::from(chain)),
+            keys: Vec::new(),
+            post: String::from(post),
+        }
+    }
+
+    /// URI of `post` relative to the forum it is found on.
+    pub fn relative(post: &str) -> FchsUri {
+        FchsUri {
+            chain: None,
+            keys: Vec::new(),
+            post: String::from(post),
+        }
+    }
+}
+
+impl FromStr for FchsUri {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scheme, rest) = s
+            .split_once(':')
+            .ok_or_else(|| uri_error("no scheme specified on URI"))?;
+        if scheme != "fchs" {
+            return Err(uri_error("invalid URI scheme"));
+        }
+
+        let mut parts: Vec<&str> = rest.split(':').collect();
+        let post = parts.pop().expect("split returns at least one part");
+        if !is_valid_hash(post) {
+            return Err(uri_error(&format!(
+                "invalid 'fchs' URI format, invalid post hash \"{}\"",
+                post
+            )));
+        }
+
+        let chain = match parts.first().copied() {
+            None => None,
+            Some("") => return Err(uri_error("invalid 'fchs' URI format, missing chain name")),
+            Some(chain) => Some(String::from(chain)),
+        };
+
+        let keys: Vec<String> = parts.iter().skip(1).map(|k| String::from(*k)).collect();
+        for key in &keys {
+            if !is_valid_key(key) {
+                return Err(uri_error(&format!(
+                    "invalid 'fchs' URI format, invalid forum key \"{}\"",
+                    key
+                )));
+            }
+        }
+
+        Ok(FchsUri {
+            chain,
+            keys,
+            post: String::from(post),
+        })
+    }
+}
+
+impl fmt::Display for FchsUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "fchs:")?;
+        if let Some(chain) = &self.chain {
+            write!(f, "{}:", chain)?;
+            for key in &self.keys {
+                write!(f, "{}:", key)?;
+            }
+        }
+        write!(f, "{}", self.post)
+    }
+}
+
+/// Checks if `hash` is a Freechains post hash, its height followed by an underscore and the hex
+/// encoded SHA-256 digest of the post.
+fn is_valid_hash(hash: &str) -> bool {
+    match hash.split_once('_') {
+        Some((height, digest)) => {
+            !height.is_empty()
+                && height.bytes().all(|b| b.is_ascii_digit())
+                && digest.len() == 64
+                && digest.bytes().all(|b| b.is_ascii_hexdigit())
+        }
+        None => false,
+    }
+}
+
+/// Checks if `key` is a hex encoded shared, public or private key.
+fn is_valid_key(key: &str) -> bool {
+    (key.len() == 64 || key.len() == 128) && key.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn uri_error(message: &str) -> Error {
+    Error::InputError(String::from(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "2_DF294EFD01B567622111B2E808FF05983B32F5A2666E9A6594A8AAE08BFA9EA4";
+    const KEY: &str = "54898902E7F3442A16E2F6CBBE2D0B24AD30A1CB964AFDEFB2E6CD08AE5C3DCF";
+
+    fn parse(uri: &str) -> Result<FchsUri, Error> {
+        uri.parse()
+    }
+
+    fn round_trip(uri: &str) -> FchsUri {
+        let parsed = parse(uri).unwrap();
+        assert_eq!(parsed.to_string(), uri);
+        parsed
+    }
+
+    #[test]
+    fn round_trips_relative_uri() {
+        assert_eq!(
+            round_trip(&format!("fchs:{}", HASH)),
+            FchsUri::relative(HASH)
+        );
+    }
+
+    #[test]
+    fn round_trips_uri_with_chain() {
+        let uri = round_trip(&format!("fchs:#forum:{}", HASH));
+        assert_eq!(uri, FchsUri::new("#forum", HASH));
+    }
+
+    #[test]
+    fn round_trips_uri_with_keys() {
+        let private_key = KEY.repeat(2);
+        let uri = round_trip(&format!("fchs:$private:{}:{}:{}", KEY, private_key, HASH));
+        assert_eq!(uri.chain.as_deref(), Some("$private"));
+        assert_eq!(uri.keys, vec![String::from(KEY), private_key]);
+        assert_eq!(uri.post, HASH);
+    }
+
+    #[test]
+    fn rejects_other_schemes() {
+        assert!(parse(&format!("http:#forum:{}", HASH)).is_err());
+        assert!(parse(HASH).is_err());
+    }
+
+    #[test]
+    fn rejects_empty_chain() {
+        assert!(parse(&format!("fchs::{}", HASH)).is_err());
+        assert!(parse(&format!("fchs::{}:{}", KEY, HASH)).is_err());
+    }
+
+    #[test]
+    fn rejects_empty_or_invalid_key() {
+        assert!(parse(&format!("fchs:$private::{}", HASH)).is_err());
+        assert!(parse(&format!("fchs:$private:{}:{}", &KEY[1..], HASH)).is_err());
+        assert!(parse(&format!("fchs:$private:{}:{}", KEY.replace('5', "G"), HASH)).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_hash() {
+        assert!(parse("fchs:#forum:").is_err());
+        assert!(parse("fchs:#forum:2").is_err());
+        assert!(parse(&format!("fchs:#forum:{}", &HASH[2..])).is_err());
+        assert!(parse(&format!("fchs:#forum:{}", &HASH[..HASH.len() - 1])).is_err());
+        assert!(parse(&format!("fchs:#forum:x{}", HASH)).is_err());
+        assert!(parse(&format!("fchs:#forum:{}", HASH.replace('D', "Z"))).is_err());
+    }
+}